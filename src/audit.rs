@@ -0,0 +1,326 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use serenity::model::prelude::UserId;
+use serenity::model::prelude::application_command::ApplicationCommandInteractionDataOption;
+
+use crate::{AuditEntry, AuditSnapshot, AuditTarget, Bracket, Match, Season, SwissTournament};
+
+const MAX_VALUE_CHARS: usize = 40;
+
+/// The command as typed, e.g. `/admin setresult matchid:M-07 winner:123`.
+pub(crate) fn command_line(name: &str, options: &[ApplicationCommandInteractionDataOption]) -> String {
+    let mut line = format!("/{}", name);
+    let mut options = options;
+    while let Some(subcommand) = options.iter().find(|o| o.value.is_none() && !o.options.is_empty()) {
+        line.push_str(format!(" {}", subcommand.name).as_str());
+        options = &subcommand.options;
+    }
+    for option in options.iter().filter(|o| o.value.is_some()) {
+        line.push_str(format!(" {}:{}", option.name, value_str(option.value.as_ref().unwrap())).as_str());
+    }
+    line
+}
+
+fn value_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// The top level fields that differ between two serialized objects, as `(before, after)` objects
+/// holding only those fields. `None` when nothing changed.
+fn changed_fields(before: &Value, after: &Value) -> Option<(Value, Value)> {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+    let mut old = Map::new();
+    let mut new = Map::new();
+    for key in before.keys().chain(after.keys().filter(|k| !before.contains_key(*k))) {
+        let (b, a) = (before.get(key).unwrap_or(&Value::Null), after.get(key).unwrap_or(&Value::Null));
+        if b != a {
+            old.insert(key.clone(), b.clone());
+            new.insert(key.clone(), a.clone());
+        }
+    }
+    if old.is_empty() { None } else { Some((Value::Object(old), Value::Object(new))) }
+}
+
+/// The items added, removed or changed between two lists of items identified by name, as
+/// `(name, before, after)`.
+fn named_changes<T: Serialize>(before: &[T], after: &[T], name: fn(&T) -> &str) -> Vec<(String, Option<Value>, Option<Value>)> {
+    let to_value = |item: &T| serde_json::to_value(item).unwrap_or(Value::Null);
+    let mut changes = Vec::new();
+    for old in before {
+        match after.iter().find(|new| name(new) == name(old)) {
+            Some(new) => {
+                if let Some((b, a)) = changed_fields(&to_value(old), &to_value(new)) {
+                    changes.push((name(old).to_string(), Some(b), Some(a)));
+                }
+            }
+            None => changes.push((name(old).to_string(), Some(to_value(old)), None)),
+        }
+    }
+    for new in after.iter().filter(|new| !before.iter().any(|old| name(old) == name(new))) {
+        changes.push((name(new).to_string(), None, Some(to_value(new))));
+    }
+    changes
+}
+
+/// One entry per match, setup, season, bracket or Swiss tournament changed between the two
+/// snapshots. Added items have no `before` and deleted or purged ones no `after`.
+pub(crate) fn audit_entries(before: &AuditSnapshot, after: &AuditSnapshot, user_id: UserId, command: &str, date: DateTime<Utc>) -> Vec<AuditEntry> {
+    let entry = |target: AuditTarget, m: Option<&Match>, before: Option<Value>, after: Option<Value>| AuditEntry {
+        date,
+        user_id,
+        command: command.to_string(),
+        target,
+        match_id: m.map(|m| m.id),
+        short_id: m.map(|m| m.short_id.clone()),
+        name: None,
+        before,
+        after,
+    };
+    let to_value = |m: &Match| serde_json::to_value(m).unwrap_or(Value::Null);
+    let mut entries = Vec::new();
+    for old in &before.matches {
+        match after.matches.iter().find(|m| m.id == old.id) {
+            Some(new) => {
+                if let Some((b, a)) = changed_fields(&to_value(old), &to_value(new)) {
+                    entries.push(entry(AuditTarget::Match, Some(new), Some(b), Some(a)));
+                }
+            }
+            None => entries.push(entry(AuditTarget::Match, Some(old), Some(to_value(old)), None)),
+        }
+    }
+    for new in after.matches.iter().filter(|m| !before.matches.iter().any(|old| old.id == m.id)) {
+        entries.push(entry(AuditTarget::Match, Some(new), None, Some(to_value(new))));
+    }
+    // Deleted matches that leave the deleted list without being restored are gone for good
+    for old in before.deleted.iter().filter(|d| !after.deleted.iter().any(|n| n.deleted_match.id == d.deleted_match.id)) {
+        if !after.matches.iter().any(|m| m.id == old.deleted_match.id) {
+            entries.push(entry(AuditTarget::DeletedMatch, Some(&old.deleted_match), serde_json::to_value(old).ok(), None));
+        }
+    }
+    let setup_value = |snapshot: &AuditSnapshot| serde_json::to_value(&snapshot.setup).unwrap_or(Value::Null);
+    if let Some((b, a)) = changed_fields(&setup_value(before), &setup_value(after)) {
+        let match_id = after.setup.match_id.or(before.setup.match_id);
+        let m = after.matches.iter().chain(before.matches.iter()).find(|m| Some(m.id) == match_id);
+        let mut setup_entry = entry(AuditTarget::Setup, m, Some(b), Some(a));
+        setup_entry.match_id = match_id;
+        entries.push(setup_entry);
+    }
+    let named = [
+        (AuditTarget::Season, named_changes(&before.seasons, &after.seasons, |s: &Season| &s.name)),
+        (AuditTarget::Bracket, named_changes(&before.brackets, &after.brackets, |b: &Bracket| &b.name)),
+        (AuditTarget::Swiss, named_changes(&before.swiss, &after.swiss, |t: &SwissTournament| &t.name)),
+    ];
+    for (target, changes) in named {
+        for (name, b, a) in changes {
+            let mut named_entry = entry(target, None, b, a);
+            named_entry.name = Some(name);
+            entries.push(named_entry);
+        }
+    }
+    entries
+}
+
+fn short_value(value: &Value) -> String {
+    let value = value_str(value);
+    if value.chars().count() > MAX_VALUE_CHARS {
+        format!("{}…", value.chars().take(MAX_VALUE_CHARS).collect::<String>())
+    } else {
+        value
+    }
+}
+
+/// A single line describing an entry: when, who, which command and the changed fields.
+pub(crate) fn print_audit_entry(entry: &AuditEntry) -> String {
+    let target = match (&entry.target, &entry.short_id) {
+        (AuditTarget::Setup, _) => String::from("setup"),
+        (AuditTarget::Match, Some(short_id)) if !short_id.is_empty() => format!("match {}", short_id),
+        (AuditTarget::Match, _) => String::from("match"),
+        (AuditTarget::DeletedMatch, Some(short_id)) => format!("deleted match {}", short_id),
+        (AuditTarget::DeletedMatch, None) => String::from("deleted match"),
+        (AuditTarget::Season, _) => format!("season {}", entry.name.clone().unwrap_or_default()),
+        (AuditTarget::Bracket, _) => format!("bracket {}", entry.name.clone().unwrap_or_default()),
+        (AuditTarget::Swiss, _) => format!("swiss {}", entry.name.clone().unwrap_or_default()),
+    };
+    let change = match (&entry.before, &entry.after) {
+        (_, None) if matches!(entry.target, AuditTarget::DeletedMatch) => String::from("purged"),
+        (None, _) => String::from("added"),
+        (_, None) => String::from("deleted"),
+        (Some(before), Some(after)) => before.as_object()
+            .map(|fields| fields.iter()
+                .map(|(key, old)| format!("{} `{}` → `{}`", key, short_value(old), short_value(&after[key])))
+                .collect::<Vec<String>>()
+                .join(", "))
+            .unwrap_or_default(),
+    };
+    format!("`{}` <@{}> `{}` {}: {}", entry.date.format("%m/%d %H:%M"), entry.user_id, entry.command, target, change)
+}
+
+/// What restoring `snapshot` over `current` would do to each match: restore deleted matches,
+/// remove matches added since and roll back changed fields.
+pub(crate) fn restore_preview(current: &[Match], snapshot: &[Match]) -> Vec<String> {
+    let to_value = |m: &Match| serde_json::to_value(m).unwrap_or(Value::Null);
+    let label = |m: &Match| format!("`{}` {} vs {}", m.short_id, m.team_one.name, m.team_two.name);
+    let mut lines = Vec::new();
+    for old in snapshot {
+        match current.iter().find(|m| m.id == old.id) {
+            Some(new) => {
+                if let Some((fields, _)) = changed_fields(&to_value(new), &to_value(old)) {
+                    let names: Vec<&String> = fields.as_object().map(|f| f.keys().collect()).unwrap_or_default();
+                    lines.push(format!("{}: rolls back {}", label(old), names.iter().map(|n| format!("`{}`", n)).collect::<Vec<String>>().join(", ")));
+                }
+            }
+            None => lines.push(format!("{}: restored", label(old))),
+        }
+    }
+    for new in current.iter().filter(|m| !snapshot.iter().any(|old| old.id == m.id)) {
+        lines.push(format!("{}: removed", label(new)));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::model::prelude::{GuildId, RoleId};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{DeletedMatch, MatchState, RolePartial, SeriesType, Setup, State};
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn new_match(short_id: &str) -> Match {
+        Match {
+            id: Uuid::new_v4(),
+            short_id: short_id.to_string(),
+            team_one: team(1),
+            team_two: team(2),
+            note: None,
+            date_added: Utc::now(),
+            match_state: MatchState::Entered,
+            schedule_info: None,
+            setup_info: None,
+            result: None,
+            series_type: None,
+            season: None,
+            division: None,
+            week: None,
+            thread_id: None,
+            lobby: None,
+            casters: Vec::new(),
+            disputes: Vec::new(),
+        }
+    }
+
+    fn snapshot(matches: Vec<Match>) -> AuditSnapshot {
+        let setup = Setup {
+            team_one: None,
+            team_two: None,
+            maps_remaining: Vec::new(),
+            maps: Vec::new(),
+            vetos: Vec::new(),
+            series_type: SeriesType::Bo3,
+            match_id: None,
+            veto_pick_order: Vec::new(),
+            current_step: 0,
+            current_phase: State::Idle,
+            decider_side_pick: None,
+        };
+        AuditSnapshot { matches, deleted: Vec::new(), setup, seasons: Vec::new(), brackets: Vec::new(), swiss: Vec::new() }
+    }
+
+    fn entries(before: &AuditSnapshot, after: &AuditSnapshot) -> Vec<AuditEntry> {
+        audit_entries(before, after, UserId(7), "/test", Utc::now())
+    }
+
+    #[test]
+    fn changed_fields_keeps_only_the_differences() {
+        let before = json!({"note": "a", "week": 1, "removed": true});
+        let after = json!({"note": "b", "week": 1, "added": 2});
+        let (old, new) = changed_fields(&before, &after).unwrap();
+        assert_eq!(old, json!({"note": "a", "removed": true, "added": null}));
+        assert_eq!(new, json!({"note": "b", "removed": null, "added": 2}));
+        assert!(changed_fields(&before, &before).is_none());
+    }
+
+    #[test]
+    fn matches_are_recorded_as_added_changed_and_deleted() {
+        let (kept, removed) = (new_match("M-01"), new_match("M-02"));
+        let before = snapshot(vec![kept.clone(), removed.clone()]);
+        let mut edited = kept.clone();
+        edited.week = Some(3);
+        let added = new_match("M-03");
+        let after = snapshot(vec![edited, added.clone()]);
+        let recorded = entries(&before, &after);
+        let changes: Vec<(Option<Uuid>, bool, bool)> = recorded.iter().map(|e| (e.match_id, e.before.is_some(), e.after.is_some())).collect();
+        assert_eq!(changes, vec![(Some(kept.id), true, true), (Some(removed.id), true, false), (Some(added.id), false, true)]);
+        assert_eq!(recorded[0].after, Some(json!({"week": 3})));
+        assert!(recorded.iter().all(|e| e.user_id == UserId(7) && e.command == "/test"));
+        assert!(entries(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn setup_changes_refer_to_the_match_being_set_up() {
+        let m = new_match("M-01");
+        let before = snapshot(vec![m.clone()]);
+        let mut after = snapshot(vec![m.clone()]);
+        after.setup.match_id = Some(m.id);
+        after.setup.current_phase = State::MapVeto;
+        let recorded = entries(&before, &after);
+        assert_eq!(recorded.len(), 1);
+        assert!(matches!(recorded[0].target, AuditTarget::Setup));
+        assert_eq!((recorded[0].match_id, recorded[0].short_id.as_deref()), (Some(m.id), Some("M-01")));
+    }
+
+    #[test]
+    fn only_matches_leaving_the_deleted_list_for_good_are_purged() {
+        let (purged, restored) = (new_match("M-01"), new_match("M-02"));
+        let tombstone = |m: &Match| DeletedMatch { deleted_match: m.clone(), deleted_by: UserId(1), date_deleted: Utc::now() };
+        let mut before = snapshot(Vec::new());
+        before.deleted = vec![tombstone(&purged), tombstone(&restored)];
+        let after = snapshot(vec![restored.clone()]);
+        let recorded = entries(&before, &after);
+        let purges: Vec<&AuditEntry> = recorded.iter().filter(|e| matches!(e.target, AuditTarget::DeletedMatch)).collect();
+        assert_eq!(purges.len(), 1);
+        assert_eq!(purges[0].match_id, Some(purged.id));
+        assert!(print_audit_entry(purges[0]).ends_with("deleted match M-01: purged"));
+    }
+
+    #[test]
+    fn seasons_are_recorded_by_name() {
+        let season = Season { name: String::from("Spring"), date_started: Utc::now(), date_archived: None, divisions: Vec::new(), seeds: Vec::new() };
+        let before = snapshot(Vec::new());
+        let mut after = snapshot(Vec::new());
+        after.seasons.push(season.clone());
+        let recorded = entries(&before, &after);
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].name.as_deref(), Some("Spring"));
+        assert!(print_audit_entry(&recorded[0]).ends_with("season Spring: added"));
+        let mut seeded = snapshot(Vec::new());
+        seeded.seasons.push(Season { seeds: vec![team(1)], ..season });
+        let recorded = entries(&after, &seeded);
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].before, Some(json!({"seeds": []})));
+    }
+
+    #[test]
+    fn restore_preview_lists_rolled_back_restored_and_removed_matches() {
+        let (kept, edited, deleted, added) = (new_match("M-01"), new_match("M-02"), new_match("M-03"), new_match("M-04"));
+        let snapshot = vec![kept.clone(), edited.clone(), deleted];
+        let current = vec![kept, Match { note: Some(String::from("Moved")), week: Some(2), ..edited }, added];
+        assert_eq!(restore_preview(&current, &snapshot), vec![
+            "`M-02` Team 1 vs Team 2: rolls back `note`, `week`",
+            "`M-03` Team 1 vs Team 2: restored",
+            "`M-04` Team 1 vs Team 2: removed",
+        ]);
+        assert!(restore_preview(&snapshot, &snapshot).is_empty());
+    }
+}
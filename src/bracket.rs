@@ -0,0 +1,404 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{Bracket, BracketNode, BracketSide, BracketSlot, BracketType, Match, MatchState, RolePartial, SeriesType};
+use crate::utils::{higher_seed, next_short_id};
+
+/// Seed numbers (1-indexed) in bracket order, so that the top seeds meet as late as possible.
+pub(crate) fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let next_len = order.len() * 2;
+        order = order.iter().flat_map(|seed| vec![*seed, next_len + 1 - seed]).collect();
+    }
+    order
+}
+
+fn new_node(bracket: &mut Bracket, side: BracketSide, round: u32, series_type: &SeriesType) -> usize {
+    let id = bracket.nodes.len();
+    bracket.nodes.push(BracketNode {
+        id,
+        side,
+        round,
+        slot_one: BracketSlot::Pending,
+        slot_two: BracketSlot::Pending,
+        series_type: series_type.clone(),
+        winner_to: None,
+        loser_to: None,
+        winner: None,
+        completed: false,
+        match_id: None,
+    });
+    id
+}
+
+fn build_upper_bracket(bracket: &mut Bracket, seeds: &[RolePartial], series_type: &SeriesType, final_type: &SeriesType) -> Vec<Vec<usize>> {
+    let size = seeds.len().next_power_of_two();
+    let order = seed_order(size);
+    let round_count = size.trailing_zeros();
+    let mut rounds: Vec<Vec<usize>> = Vec::new();
+    for round in 1..=round_count {
+        let node_count = size >> round;
+        let round_type = if round == round_count { final_type } else { series_type };
+        let nodes: Vec<usize> = (0..node_count).map(|_| new_node(bracket, BracketSide::Upper, round, round_type)).collect();
+        if let Some(previous) = rounds.last() {
+            for (i, node) in previous.iter().enumerate() {
+                bracket.nodes[*node].winner_to = Some((nodes[i / 2], if i % 2 == 0 { 1 } else { 2 }));
+            }
+        }
+        rounds.push(nodes);
+    }
+    for (i, node) in rounds[0].iter().enumerate() {
+        let slot = |seed: usize| seeds.get(seed - 1).cloned().map(BracketSlot::Team).unwrap_or(BracketSlot::Bye);
+        bracket.nodes[*node].slot_one = slot(order[i * 2]);
+        bracket.nodes[*node].slot_two = slot(order[i * 2 + 1]);
+    }
+    rounds
+}
+
+/// Builds a single elimination bracket from teams ordered by seed.
+pub(crate) fn build_single_elimination(name: String, season: Option<String>, seeds: &[RolePartial], series_type: &SeriesType, final_type: &SeriesType, third_place: bool) -> Bracket {
+    let mut bracket = Bracket { name, season, bracket_type: BracketType::SingleElimination, seeds: seeds.to_vec(), nodes: Vec::new() };
+    let rounds = build_upper_bracket(&mut bracket, seeds, series_type, final_type);
+    if third_place && rounds.len() >= 2 {
+        let semifinals = rounds[rounds.len() - 2].clone();
+        let round = rounds.len() as u32;
+        let third_place_node = new_node(&mut bracket, BracketSide::ThirdPlace, round, series_type);
+        bracket.nodes[semifinals[0]].loser_to = Some((third_place_node, 1));
+        bracket.nodes[semifinals[1]].loser_to = Some((third_place_node, 2));
+    }
+    resolve_byes(&mut bracket);
+    bracket
+}
+
+/// Builds a double elimination bracket from teams ordered by seed. Upper bracket losers drop
+/// into the lower bracket, and the grand final is played once between both bracket winners.
+pub(crate) fn build_double_elimination(name: String, season: Option<String>, seeds: &[RolePartial], series_type: &SeriesType, final_type: &SeriesType) -> Bracket {
+    let mut bracket = Bracket { name, season, bracket_type: BracketType::DoubleElimination, seeds: seeds.to_vec(), nodes: Vec::new() };
+    let upper = build_upper_bracket(&mut bracket, seeds, series_type, final_type);
+    let round_count = upper.len();
+    let mut lower_round = 1;
+    let mut lower: Vec<usize> = (0..upper[0].len() / 2).map(|_| new_node(&mut bracket, BracketSide::Lower, lower_round, series_type)).collect();
+    for (i, node) in upper[0].iter().enumerate() {
+        bracket.nodes[*node].loser_to = Some((lower[i / 2], if i % 2 == 0 { 1 } else { 2 }));
+    }
+    for (upper_index, upper_round) in upper.iter().enumerate().skip(1) {
+        lower_round += 1;
+        let round_type = if upper_index == round_count - 1 { final_type } else { series_type };
+        let drop_round: Vec<usize> = (0..upper_round.len()).map(|_| new_node(&mut bracket, BracketSide::Lower, lower_round, round_type)).collect();
+        for (i, node) in lower.iter().enumerate() {
+            bracket.nodes[*node].winner_to = Some((drop_round[i], 1));
+        }
+        // Dropped teams are fed in reverse order to delay rematches from the upper bracket
+        for (i, node) in upper_round.iter().enumerate() {
+            bracket.nodes[*node].loser_to = Some((drop_round[upper_round.len() - 1 - i], 2));
+        }
+        lower = drop_round;
+        if lower.len() > 1 {
+            lower_round += 1;
+            let next_round: Vec<usize> = (0..lower.len() / 2).map(|_| new_node(&mut bracket, BracketSide::Lower, lower_round, series_type)).collect();
+            for (i, node) in lower.iter().enumerate() {
+                bracket.nodes[*node].winner_to = Some((next_round[i / 2], if i % 2 == 0 { 1 } else { 2 }));
+            }
+            lower = next_round;
+        }
+    }
+    let grand_final = new_node(&mut bracket, BracketSide::GrandFinal, 1, final_type);
+    bracket.nodes[upper[round_count - 1][0]].winner_to = Some((grand_final, 1));
+    bracket.nodes[lower[0]].winner_to = Some((grand_final, 2));
+    resolve_byes(&mut bracket);
+    bracket
+}
+
+fn fill_slot(bracket: &mut Bracket, target: Option<(usize, u8)>, value: BracketSlot) {
+    if let Some((node, slot)) = target {
+        if slot == 1 {
+            bracket.nodes[node].slot_one = value;
+        } else {
+            bracket.nodes[node].slot_two = value;
+        }
+    }
+}
+
+/// Advances teams through every node that can not be played because one or both sides are byes.
+fn resolve_byes(bracket: &mut Bracket) {
+    loop {
+        let bye_node = bracket.nodes.iter().position(|n| !n.completed
+            && n.slot_one != BracketSlot::Pending
+            && n.slot_two != BracketSlot::Pending
+            && (n.slot_one == BracketSlot::Bye || n.slot_two == BracketSlot::Bye));
+        let index = match bye_node {
+            Some(index) => index,
+            None => break,
+        };
+        let node = bracket.nodes[index].clone();
+        let advancing = match (&node.slot_one, &node.slot_two) {
+            (BracketSlot::Team(team), _) | (_, BracketSlot::Team(team)) => Some(team.clone()),
+            _ => None,
+        };
+        bracket.nodes[index].completed = true;
+        bracket.nodes[index].winner = advancing.clone();
+        fill_slot(bracket, node.winner_to, advancing.map(BracketSlot::Team).unwrap_or(BracketSlot::Bye));
+        fill_slot(bracket, node.loser_to, BracketSlot::Bye);
+    }
+}
+
+pub(crate) fn node_label(bracket: &Bracket, node: &BracketNode) -> String {
+    let last_round = bracket.nodes.iter().filter(|n| n.side == node.side).map(|n| n.round).max().unwrap_or(1);
+    match node.side {
+        BracketSide::ThirdPlace => String::from("Third Place"),
+        BracketSide::GrandFinal => String::from("Grand Final"),
+        BracketSide::Upper if bracket.bracket_type == BracketType::SingleElimination && node.round == last_round => String::from("Final"),
+        BracketSide::Upper if bracket.bracket_type == BracketType::SingleElimination && node.round + 1 == last_round => String::from("Semifinal"),
+        BracketSide::Upper if node.round == last_round => String::from("Upper Final"),
+        BracketSide::Lower if node.round == last_round => String::from("Lower Final"),
+        BracketSide::Upper if bracket.bracket_type == BracketType::SingleElimination => format!("Round {}", node.round),
+        BracketSide::Upper => format!("Upper Round {}", node.round),
+        BracketSide::Lower => format!("Lower Round {}", node.round),
+    }
+}
+
+/// Creates a `Match` for every node that has both teams but no match yet. With `higher_seed_home`
+/// the higher seed is always listed as team one.
+pub(crate) fn create_bracket_matches(bracket: &mut Bracket, matches: &mut Vec<Match>, last_id: &mut u32, higher_seed_home: bool) -> Vec<Match> {
+    let mut created = Vec::new();
+    for index in 0..bracket.nodes.len() {
+        let node = bracket.nodes[index].clone();
+        if node.completed || node.match_id.is_some() { continue; }
+        if let (BracketSlot::Team(team_one), BracketSlot::Team(team_two)) = (&node.slot_one, &node.slot_two) {
+            let (team_one, team_two) = if higher_seed_home && higher_seed(&bracket.seeds, team_one, team_two).is_some_and(|t| t.id == team_two.id) {
+                (team_two, team_one)
+            } else {
+                (team_one, team_two)
+            };
+            let new_match = Match {
+                id: Uuid::new_v4(),
+                short_id: next_short_id(last_id),
+                team_one: team_one.clone(),
+                team_two: team_two.clone(),
+                note: Some(format!("{} - {}", bracket.name, node_label(bracket, &node))),
+                date_added: Utc::now(),
+                match_state: MatchState::Entered,
+                schedule_info: None,
+                setup_info: None,
+                result: None,
+                series_type: Some(node.series_type.clone()),
+                season: bracket.season.clone(),
+                division: None,
+                week: None,
+                thread_id: None,
+                lobby: None,
+                casters: Vec::new(),
+                disputes: Vec::new(),
+            };
+            bracket.nodes[index].match_id = Some(new_match.id);
+            created.push(new_match.clone());
+            matches.push(new_match);
+        }
+    }
+    created
+}
+
+/// A node that was completed without a match because one side is a bye.
+fn is_bye_node(node: &BracketNode) -> bool {
+    node.completed && node.match_id.is_none()
+}
+
+fn replace_team(bracket: &mut Bracket, matches: &mut [Match], target: Option<(usize, u8)>, team: &RolePartial) {
+    if let Some((node, slot)) = target {
+        if is_bye_node(&bracket.nodes[node]) {
+            bracket.nodes[node].winner = Some(team.clone());
+            fill_slot(bracket, target, BracketSlot::Team(team.clone()));
+            let winner_to = bracket.nodes[node].winner_to;
+            replace_team(bracket, matches, winner_to, team);
+            return;
+        }
+        let previous = if slot == 1 { bracket.nodes[node].slot_one.clone() } else { bracket.nodes[node].slot_two.clone() };
+        if let (Some(match_id), BracketSlot::Team(previous)) = (bracket.nodes[node].match_id, previous) {
+            if let Some(m) = matches.iter_mut().find(|m| m.id == match_id) {
+                if m.team_one.id == previous.id { m.team_one = team.clone(); } else { m.team_two = team.clone(); }
+            }
+        }
+    }
+    fill_slot(bracket, target, BracketSlot::Team(team.clone()));
+}
+
+/// Whether the team moved into `target` has already played on. A bye passes the team straight
+/// through, so the node after it is checked instead.
+fn is_target_locked(bracket: &Bracket, matches: &[Match], target: Option<(usize, u8)>) -> bool {
+    if let Some((node, _)) = target {
+        let node = &bracket.nodes[node];
+        if is_bye_node(node) { return is_target_locked(bracket, matches, node.winner_to); }
+        if node.completed { return true; }
+        if let Some(match_id) = node.match_id {
+            return matches.iter().any(|m| m.id == match_id && (m.result.is_some() || m.setup_info.is_some()));
+        }
+    }
+    false
+}
+
+/// Moves the winner and loser of a bracket match into their next matches and creates any matches
+/// that became playable. Returns the created matches, or `None` if the match is not part of a bracket.
+pub(crate) fn advance_bracket(brackets: &mut [Bracket], matches: &mut Vec<Match>, last_id: &mut u32, match_id: Uuid, winner: &RolePartial, higher_seed_home: bool) -> Option<Result<Vec<Match>, String>> {
+    let bracket = brackets.iter_mut().find(|b| b.nodes.iter().any(|n| n.match_id == Some(match_id)))?;
+    let index = bracket.nodes.iter().position(|n| n.match_id == Some(match_id)).unwrap();
+    let node = bracket.nodes[index].clone();
+    let loser = match (&node.slot_one, &node.slot_two) {
+        (BracketSlot::Team(one), BracketSlot::Team(two)) => if one.id == winner.id { two.clone() } else { one.clone() },
+        _ => return Some(Err(String::from("This bracket match is missing a team"))),
+    };
+    if let Some(previous) = &node.winner {
+        if previous.id == winner.id {
+            return Some(Ok(Vec::new()));
+        }
+        if is_target_locked(bracket, matches, node.winner_to) || is_target_locked(bracket, matches, node.loser_to) {
+            return Some(Err(String::from("The next bracket match has already started. Its result must be changed first")));
+        }
+        bracket.nodes[index].winner = Some(winner.clone());
+        replace_team(bracket, matches, node.winner_to, winner);
+        replace_team(bracket, matches, node.loser_to, &loser);
+        return Some(Ok(Vec::new()));
+    }
+    bracket.nodes[index].winner = Some(winner.clone());
+    bracket.nodes[index].completed = true;
+    fill_slot(bracket, node.winner_to, BracketSlot::Team(winner.clone()));
+    fill_slot(bracket, node.loser_to, BracketSlot::Team(loser));
+    resolve_byes(bracket);
+    Some(Ok(create_bracket_matches(bracket, matches, last_id, higher_seed_home)))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serenity::model::prelude::{GuildId, RoleId};
+
+    use super::*;
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn seeds(count: u64) -> Vec<RolePartial> {
+        (1..=count).map(team).collect()
+    }
+
+    fn slot_team(slot: &BracketSlot) -> Option<RoleId> {
+        match slot {
+            BracketSlot::Team(team) => Some(team.id),
+            _ => None,
+        }
+    }
+
+    /// Reports `winner` for the match of `node` and returns the matches that became playable.
+    fn report(brackets: &mut [Bracket], matches: &mut Vec<Match>, last_id: &mut u32, node: usize, winner: u64) -> Result<Vec<Match>, String> {
+        let match_id = brackets[0].nodes[node].match_id.expect("node has a match");
+        advance_bracket(brackets, matches, last_id, match_id, &team(winner), false).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn seed_order_pairs_top_and_bottom_seeds(exponent in 0u32..7) {
+            let size = 1usize << exponent;
+            let order = seed_order(size);
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            prop_assert_eq!(sorted, (1..=size).collect::<Vec<_>>());
+            if size >= 2 {
+                for pair in order.chunks(2) {
+                    prop_assert_eq!(pair[0] + pair[1], size + 1);
+                }
+                let top_half = &order[..size / 2];
+                prop_assert!(top_half.contains(&1) && !top_half.contains(&2));
+            }
+        }
+    }
+
+    #[test]
+    fn double_elimination_feeds_every_slot_once() {
+        let bracket = build_double_elimination(String::from("Playoffs"), None, &seeds(8), &SeriesType::Bo3, &SeriesType::Bo5);
+        assert_eq!(bracket.nodes.len(), 14);
+        let mut fed = Vec::new();
+        for node in &bracket.nodes {
+            fed.extend(node.winner_to);
+            fed.extend(node.loser_to);
+            match node.side {
+                BracketSide::Upper => {
+                    let (target, _) = node.loser_to.expect("upper losers drop to the lower bracket");
+                    assert!(bracket.nodes[target].side == BracketSide::Lower);
+                }
+                BracketSide::Lower => assert!(node.loser_to.is_none()),
+                _ => {}
+            }
+        }
+        let grand_final = bracket.nodes.iter().position(|n| n.side == BracketSide::GrandFinal).unwrap();
+        assert!(bracket.nodes[grand_final].winner_to.is_none());
+        let first_round = bracket.nodes.iter().filter(|n| n.side == BracketSide::Upper && n.round == 1).count();
+        let mut expected: Vec<(usize, u8)> = bracket.nodes.iter()
+            .filter(|n| !(n.side == BracketSide::Upper && n.round == 1))
+            .flat_map(|n| vec![(n.id, 1), (n.id, 2)])
+            .collect();
+        assert_eq!(first_round, 4);
+        fed.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(fed, expected);
+        let upper_final = bracket.nodes.iter().find(|n| n.winner_to == Some((grand_final, 1))).unwrap();
+        let lower_final = bracket.nodes.iter().find(|n| n.winner_to == Some((grand_final, 2))).unwrap();
+        assert!(upper_final.side == BracketSide::Upper && lower_final.side == BracketSide::Lower);
+    }
+
+    #[test]
+    fn byes_advance_the_top_seeds() {
+        let bracket = build_single_elimination(String::from("Playoffs"), None, &seeds(5), &SeriesType::Bo3, &SeriesType::Bo3, false);
+        let first_round: Vec<&BracketNode> = bracket.nodes.iter().filter(|n| n.round == 1).collect();
+        let byes: Vec<RoleId> = first_round.iter().filter(|n| n.completed).map(|n| n.winner.as_ref().unwrap().id).collect();
+        assert_eq!(byes, vec![RoleId(1), RoleId(2), RoleId(3)]);
+        assert_eq!(first_round.iter().filter(|n| !n.completed).count(), 1);
+        let second_round: Vec<(Option<RoleId>, Option<RoleId>)> = bracket.nodes.iter()
+            .filter(|n| n.round == 2)
+            .map(|n| (slot_team(&n.slot_one), slot_team(&n.slot_two)))
+            .collect();
+        assert_eq!(second_round, vec![(Some(RoleId(1)), None), (Some(RoleId(2)), Some(RoleId(3)))]);
+    }
+
+    #[test]
+    fn overriding_a_result_moves_the_new_winner_on() {
+        let mut brackets = vec![build_single_elimination(String::from("Playoffs"), None, &seeds(4), &SeriesType::Bo3, &SeriesType::Bo3, false)];
+        let (mut matches, mut last_id) = (Vec::new(), 0);
+        assert_eq!(create_bracket_matches(&mut brackets[0], &mut matches, &mut last_id, false).len(), 2);
+        report(&mut brackets, &mut matches, &mut last_id, 0, 1).unwrap();
+        let created = report(&mut brackets, &mut matches, &mut last_id, 1, 2).unwrap();
+        assert_eq!(created.len(), 1);
+        report(&mut brackets, &mut matches, &mut last_id, 0, 4).unwrap();
+        let final_match = matches.iter().find(|m| m.id == created[0].id).unwrap();
+        assert!(final_match.team_one.id == RoleId(4) && final_match.team_two.id == RoleId(2));
+        assert_eq!(slot_team(&brackets[0].nodes[2].slot_one), Some(RoleId(4)));
+        assert_eq!(last_id, 3);
+    }
+
+    #[test]
+    fn overriding_a_result_is_rejected_once_the_next_match_is_played() {
+        let mut brackets = vec![build_single_elimination(String::from("Playoffs"), None, &seeds(4), &SeriesType::Bo3, &SeriesType::Bo3, false)];
+        let (mut matches, mut last_id) = (Vec::new(), 0);
+        create_bracket_matches(&mut brackets[0], &mut matches, &mut last_id, false);
+        report(&mut brackets, &mut matches, &mut last_id, 0, 1).unwrap();
+        report(&mut brackets, &mut matches, &mut last_id, 1, 2).unwrap();
+        report(&mut brackets, &mut matches, &mut last_id, 2, 1).unwrap();
+        assert!(report(&mut brackets, &mut matches, &mut last_id, 0, 4).is_err());
+    }
+
+    #[test]
+    fn overriding_a_result_passes_through_a_bye() {
+        let mut brackets = vec![build_double_elimination(String::from("Playoffs"), None, &seeds(3), &SeriesType::Bo3, &SeriesType::Bo3)];
+        let (mut matches, mut last_id) = (Vec::new(), 0);
+        create_bracket_matches(&mut brackets[0], &mut matches, &mut last_id, false);
+        report(&mut brackets, &mut matches, &mut last_id, 1, 2).unwrap();
+        let (bye_node, _) = brackets[0].nodes[1].loser_to.unwrap();
+        assert!(brackets[0].nodes[bye_node].completed);
+        let (dropped_to, slot) = brackets[0].nodes[bye_node].winner_to.unwrap();
+        assert_eq!(slot, 1);
+        assert_eq!(slot_team(&brackets[0].nodes[dropped_to].slot_one), Some(RoleId(3)));
+        report(&mut brackets, &mut matches, &mut last_id, 1, 3).unwrap();
+        assert_eq!(brackets[0].nodes[bye_node].winner.as_ref().map(|t| t.id), Some(RoleId(2)));
+        assert_eq!(slot_team(&brackets[0].nodes[dropped_to].slot_one), Some(RoleId(2)));
+    }
+}
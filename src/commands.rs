@@ -1,26 +1,53 @@
+use std::borrow::Cow;
 use std::str::FromStr;
 use chrono::{NaiveDate, Utc};
 
 
+use serenity::futures::StreamExt;
 use serenity::client::Context;
 use serenity::model::interactions::application_command::ApplicationCommandInteraction;
-use serenity::model::prelude::application_command::ApplicationCommandInteractionDataOptionValue;
-use serenity::model::prelude::Role;
+use serenity::model::interactions::autocomplete::AutocompleteInteraction;
+use serenity::model::interactions::message_component::MessageComponentInteraction;
+use serenity::model::prelude::InteractionResponseType;
+use serenity::model::prelude::application_command::{ApplicationCommandInteractionDataOption, ApplicationCommandInteractionDataOptionValue};
+use serenity::http::AttachmentType;
+use serenity::model::prelude::{GuildId, Role, RoleId};
 use serenity::utils::MessageBuilder;
 use uuid::Uuid;
 
-use crate::{Setup, Maps, Match, Matches, MatchState, RolePartial, ScheduleInfo, SeriesType, SetupStep, SeriesMap};
+use crate::{AuditEntry, Caster, DeletedMatch, DeletedMatches, CommandError, Dispute, DisputeOutcome, DisputeResolution, CommandResult, Config, GuildData, MatchFilter, MatchPages, Response, Setup, Match, MatchCounter, Matches, MatchState, RolePartial, ScheduleInfo, SeriesType, MatchResult, ResultType, Season, Seasons, Division, PendingMatches, MapScore, Bracket, Brackets, SwissTournament, SwissTournaments};
+use crate::swiss::{pair_next_round, swiss_records};
+use crate::SeriesType::{Bo3, Bo5};
+use crate::bracket::{build_double_elimination, build_single_elimination, create_bracket_matches};
+use crate::render::{bracket_png, bracket_text};
+use crate::round_robin::round_robin_rounds;
+use crate::stats::{league_map_stats, team_map_stats};
 use crate::MatchState::Completed;
 use crate::State::{Idle, MapVeto, SidePick};
-use crate::StepType::{Pick, Veto};
-use crate::utils::{admin_check, write_to_file, find_user_team_role, is_phase_allowed, user_team, eos_printout, get_maps, reset_setup, finish_setup, print_veto_info, print_match_info};
+use crate::veto::{apply_map_step, apply_side_pick, current_turn, series_length, side_picker, start_veto, Side, Turn, VetoProgress};
+use crate::audit::{print_audit_entry, restore_preview};
+use crate::utils::{admin_check, audit_snapshot, queue_audit, thread_parent, create_backup, prune_backups, guild_backups, read_audit, read_backup_file, read_backup_matches, broadcast_schedule, close_match_thread, create_match_lobby, schedule_lobby_cleanup, set_lobby_caster, guild_file, guild_id, required_string, required_role, write_to_file, find_user_team_role, is_phase_allowed, user_team, eos_embed, get_maps, guild_maps, reset_setup, finish_setup, print_veto_info, print_match_info, find_option, week_option, print_standings, is_match_id, next_short_id, save_match_counter, short_id_number, current_season, find_division, update_brackets, team_seeds, higher_seed, print_map_stats, print_team_stats, team_colour, match_embed, veto_embed, matches_page_embed, page_buttons, filter_matches};
 
 
-pub(crate) async fn handle_help(context: &Context, msg: &ApplicationCommandInteraction) -> String {
+const MATCHES_PER_PAGE: usize = 10;
+const MAX_MATCH_PAGES: usize = 100;
+
+pub(crate) async fn handle_help(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
     let mut commands = String::from("
 `/setup` - start user's team's next match setup
 `/schedule` - schedule match
 `/matches` - list matches
+`/standings` - show team standings
+`/bracket` - show a playoff bracket as an image and text tree
+`/seeds` - show team seeds
+`/mapstats` - show league wide map pick and ban rates
+`/teamstats` - show a team's veto tendencies and map results
+`/h2h` - show the head-to-head history of two teams
+`/cast` - claim a scheduled match to cast (caster role required)
+`/dispute` - open a dispute ticket for your team's match
+`/broadcasts` - show the cast matches of the next 7 days
+`/report` - report your match's map scores
+`/swiss` - show Swiss tournament standings
 `/maps` - list maps
 `/defense` - pick defense side during side pick phase
 `/attack`- pick attack side during side pick phase
@@ -32,10 +59,26 @@ pub(crate) async fn handle_help(context: &Context, msg: &ApplicationCommandInter
 _These are privileged admin commands:_
 `/addmatch` - add match to schedule
 `/deletematch`- delete match from schedule
+`/restorematch` - restore a deleted match
+`/purgematch` - permanently remove a deleted match
+`/restore` - preview or restore this server's data from a backup
+`/editmatch` - edit match teams, note, series type, schedule or state
+`/forfeit` - record a forfeit or no-show
+`/admin setresult` - override a match result
+`/admin resolvedispute` - record the decision on a match dispute
+`/audit` - show the change history of a match
+`/admin newseason` - start a new season and archive the current one
+`/admin adddivision` - add a division to the current season
+`/admin addteam` - add a team to a division
+`/admin seed` - set a team's seed
+`/admin roundrobin` - preview a round robin schedule for a division
+`/admin confirmschedule` - add the previewed schedule
+`/admin bracket` - create a playoff bracket from the seeds
+`/admin swiss` - start a Swiss tournament
+`/admin swissnext` - close the Swiss round and pair the next one
 `/cancel` - cancel setup
     ");
-    let admin_check = admin_check(context, msg).await;
-    if let Ok(_result_str) = admin_check {
+    if admin_check(context, msg).await.is_ok() {
         commands.push_str(&admin_commands)
     }
     let response = MessageBuilder::new()
@@ -48,23 +91,18 @@ _These are privileged admin commands:_
     } else {
         eprintln!("Error sending .help dm");
     }
-    String::from("Help info sent via DM")
+    Ok(Response::ephemeral(String::from("Help info sent via DM")))
 }
 
-pub(crate) async fn handle_setup(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let option = msg.data
-        .options
-        .get(0)
-        .expect("Expected match type option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
+pub(crate) async fn handle_setup(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let option = find_option(&msg.data.options, "type");
 
     let mut next_match = None;
-    if let Ok(roles) = context.http.get_guild_roles(*msg.guild_id.unwrap().as_u64()).await {
+    if let Ok(roles) = context.http.get_guild_roles(*guild_id.as_u64()).await {
         if let Ok(team_role) = find_user_team_role(roles, &msg.user, &context).await {
             let mut data = context.data.write().await;
-            let matches: &mut Vec<Match> = data.get_mut::<Matches>().unwrap();
+            let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
             for m in matches.iter_mut() {
                 if m.match_state == Completed { continue; }
                 if m.team_one.id != team_role.id && m.team_two.id != team_role.id { continue; }
@@ -73,296 +111,216 @@ pub(crate) async fn handle_setup(context: &Context, msg: &ApplicationCommandInte
             }
         }
     } else {
-        return String::from("You are not part of any team. Verify you have a role starting with `Team`");
+        return Err(CommandError::User(String::from("You are not part of any team. Verify you have a role starting with `Team`")));
     }
     if next_match.is_none() {
-        return String::from("Your team does not have any scheduled matches");
+        return Err(CommandError::User(String::from("Your team does not have any scheduled matches")));
     }
     let current_match = next_match.unwrap();
-    let mut data = context.data.write().await;
-    let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-    if let ApplicationCommandInteractionDataOptionValue::String(match_type) = option {
-        setup.series_type = SeriesType::from_str(match_type).unwrap();
+    let mut series_type = current_match.series_type.clone();
+    let mut ignored_type = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(match_type)) = option {
+        let requested = SeriesType::from_str(match_type).ok();
+        if series_type.is_none() {
+            series_type = requested;
+        } else if requested.is_some() && requested != series_type {
+            ignored_type = requested;
+        }
     }
-    setup.team_one = Some(current_match.team_one.clone());
-    setup.team_two = Some(current_match.team_two.clone());
-    setup.match_id = Some(current_match.id);
-    setup.current_step = 0;
-    setup.current_phase = MapVeto;
-    let map_str: String = setup.maps_remaining.iter().map(|map| format!("- `{}`\n", map.to_uppercase())).collect();
-    if let ApplicationCommandInteractionDataOptionValue::String(match_type) = option {
-        let mut result = match SeriesType::from_str(match_type).unwrap() {
-            SeriesType::Bo1 => { handle_bo1_setup(msg, setup.clone()).await }
-            SeriesType::Bo3 => { handle_bo3_setup(msg, setup.clone()).await }
-            SeriesType::Bo5 => { handle_bo5_setup(msg, setup.clone()).await }
-        };
-        setup.veto_pick_order = result.0;
-        result.1.push_str("Remaining maps:\n");
-        result.1.push_str(map_str.as_str());
-        return result.1;
-    }
-    String::from("Setup encountered an error")
-}
-
-pub(crate) async fn handle_bo1_setup(_msg: &ApplicationCommandInteraction, setup: Setup) -> (Vec<SetupStep>, String) {
-    return (vec![
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-    ], format!("Best of 1 option selected. Starting map veto. <@&{}> bans first.\n", &setup.team_one.unwrap().id));
-}
-
-pub(crate) async fn handle_bo3_setup(_msg: &ApplicationCommandInteraction, setup: Setup) -> (Vec<SetupStep>, String) {
-    return (vec![
-        SetupStep { step_type: Veto, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-    ], format!("Best of 3 option selected. Starting map veto. <@&{}> bans first.\n", &setup.team_one.unwrap().id));
-}
-
-pub(crate) async fn handle_bo5_setup(_msg: &ApplicationCommandInteraction, setup: Setup) -> (Vec<SetupStep>, String) {
-    return (vec![
-        SetupStep { step_type: Veto, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Veto, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_two.unwrap(), map: None },
-        SetupStep { step_type: Pick, team: setup.clone().team_one.unwrap(), map: None },
-    ], format!("Best of 5 option selected. Starting map veto. <@&{}> bans first.\n", &setup.team_one.unwrap().id));
-}
-
-pub(crate) async fn handle_defense_option(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    if let Err(_err) = is_phase_allowed(context, msg, SidePick).await {
-        return String::from("It is not the side pick phase");
+    if series_type.is_none() {
+        return Err(CommandError::User(String::from("This match has no series type set. Select one with the `type` option")));
     }
-    let mut resp = String::new();
-    if let Ok(user_role_partial) = user_team(context, msg).await {
-        let mut data = context.data.write().await;
-        let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-        if setup.maps[setup.current_step].picked_by == user_role_partial {
-            return String::from("It is not your turn to pick sides");
-        }
-        let picked_role_id = user_role_partial.id;
-        setup.maps[setup.current_step].start_defense = Some(user_role_partial);
-        setup.maps[setup.current_step].start_attack = if setup.clone().team_two.unwrap().id == setup.maps[setup.current_step].start_defense.clone().unwrap().id {
-            setup.clone().team_one
-        } else {
-            setup.clone().team_two
-        };
-        if setup.maps.len() - 1 > setup.current_step {
-            let next_pick = if setup.clone().team_two.unwrap().id == setup.maps[setup.current_step + 1].picked_by.id {
-                setup.clone().team_one
-            } else {
-                setup.clone().team_two
-            };
-            resp = format!("<@&{}> picked to start `defense` on `{}`. It is now <@&{}>'s turn to pick starting side on `{}`", &picked_role_id, setup.maps[setup.current_step].map.to_uppercase(), next_pick.unwrap().id, setup.maps[setup.current_step + 1].map.to_uppercase());
-            setup.current_step += 1;
-            return resp;
-        } else {
-            resp = format!("<@&{}> picked to start `attack` on `{}`", &picked_role_id, setup.maps[setup.current_step].map.to_uppercase());
-            resp.push_str(eos_printout(setup.clone()).as_str());
-        };
-    }
-    finish_setup(context).await;
-    if resp == String::new() {
-        return String::from("There was an issue processing this option");
+    let mut data = context.data.write().await;
+    let config = data.get::<Config>().unwrap();
+    let guild_config = config.guild(guild_id);
+    let seeding = guild_config.seeding.unwrap_or_default();
+    let (veto_first, decider_side) = (seeding.veto_first, seeding.decider_side);
+    let templates = guild_config.veto;
+    let season = data.guild::<Seasons>(guild_id).iter().find(|s| Some(&s.name) == current_match.season.as_ref());
+    let season_matches: Vec<Match> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| m.season == current_match.season)
+        .cloned()
+        .collect();
+    let seeds = team_seeds(season, &season_matches);
+    let higher = higher_seed(&seeds, &current_match.team_one, &current_match.team_two).cloned();
+    let before = audit_snapshot(&data, guild_id);
+    let setup: &mut Setup = data.guild_mut::<Setup>(guild_id);
+    let first = if veto_first { higher.as_ref() } else { None };
+    let series_type = series_type.unwrap();
+    start_veto(setup, series_type.clone(), &templates, (current_match.team_one.clone(), current_match.team_two.clone()), first)?;
+    setup.match_id = Some(current_match.id);
+    setup.decider_side_pick = if decider_side { higher.clone() } else { None };
+    let mut resp = format!("Best of {} option selected. Starting map veto. <@&{}> bans first.\n", series_length(&series_type), setup.veto_pick_order[0].team.id);
+    if let Some(ignored) = ignored_type {
+        resp.push_str(format!("Note: this match is set to best of {}, so the requested best of {} was ignored. An admin can change it with `/editmatch`.\n", series_length(&series_type), series_length(&ignored)).as_str());
     }
-    resp
+    resp.push_str("Remaining maps:\n");
+    let map_str: String = setup.maps_remaining.iter().map(|map| format!("- `{}`\n", map.to_uppercase())).collect();
+    resp.push_str(map_str.as_str());
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
 }
 
-pub(crate) async fn handle_attack_option(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    if let Err(_err) = is_phase_allowed(context, msg, SidePick).await {
-        return String::from("It is not the side pick phase");
-    }
-    let mut resp = String::new();
-    if let Ok(user_role_partial) = user_team(context, msg).await {
-        let mut data = context.data.write().await;
-        let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-        if setup.maps[setup.current_step].picked_by == user_role_partial {
-            return String::from("It is not your turn to pick sides");
-        }
-        let picked_role_id = user_role_partial.id;
-        setup.maps[setup.current_step].start_attack = Some(user_role_partial);
-        setup.maps[setup.current_step].start_defense = if setup.clone().team_two.unwrap().id == setup.maps[setup.current_step].start_attack.clone().unwrap().id {
-            setup.clone().team_one
-        } else {
-            setup.clone().team_two
-        };
-        if setup.maps.len() - 1 > setup.current_step {
-            let next_pick = if setup.clone().team_two.unwrap().id == setup.maps[setup.current_step + 1].picked_by.id {
-                setup.clone().team_one
-            } else {
-                setup.clone().team_two
-            };
-            resp = format!("<@&{}> picked to start `attack` on `{}`. It is now <@&{}>'s turn to pick starting side on `{}`", &picked_role_id, setup.maps[setup.current_step].map.to_uppercase(), next_pick.unwrap().id, setup.maps[setup.current_step + 1].map.to_uppercase());
-            setup.current_step += 1;
-            return resp;
-        } else {
-            resp = format!("<@&{}> picked to start `attack` on `{}`", &picked_role_id, setup.maps[setup.current_step].map.to_uppercase());
-            resp.push_str(eos_printout(setup.clone()).as_str());
-        };
-    }
-    finish_setup(context).await;
-    if resp == String::new() {
-        return String::from("There was an issue processing this option");
-    }
-    resp
+pub(crate) async fn handle_defense_option(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    handle_side_option(context, msg, Side::Defense).await
 }
 
+pub(crate) async fn handle_attack_option(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    handle_side_option(context, msg, Side::Attack).await
+}
 
-pub(crate) async fn handle_pick_option(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    if let Err(err) = is_phase_allowed(context, msg, MapVeto).await {
-        return err;
-    }
-    {
-        let data = context.data.write().await;
-        let setup: &Setup = data.get::<Setup>().unwrap();
-        if setup.veto_pick_order.get(setup.current_step).unwrap().step_type != Pick {
-            return String::from("It is not your turn to pick");
-        }
+async fn handle_side_option(context: &Context, msg: &ApplicationCommandInteraction, side: Side) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    if is_phase_allowed(context, msg, SidePick).await.is_err() {
+        return Err(CommandError::User(String::from("It is not the side pick phase")));
     }
-    if let Ok(user_role_partial) = user_team(context, msg).await {
+    let user_role_partial = user_team(context, msg).await?;
+    let (resp, finished_setup) = {
         let mut data = context.data.write().await;
-        let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-        if setup.veto_pick_order.get(setup.current_step).unwrap().team.id != user_role_partial.id {
-            return String::from("It is not your turn to pick");
-        }
-        let option = msg.data
-            .options
-            .get(0)
-            .expect("Expected map name option")
-            .resolved
-            .as_ref()
-            .expect("Expected object");
-        if let ApplicationCommandInteractionDataOptionValue::String(map) = option {
-            if !setup.maps_remaining.contains(map) {
-                return String::from("Select a remaining map");
-            }
-            setup.veto_pick_order[setup.current_step].map = Some(String::from(map));
-            let map_index = setup.maps_remaining.iter().position(|m| m == map).unwrap();
-            setup.maps_remaining.remove(map_index);
-            let picked_by_team = setup.veto_pick_order[setup.current_step].team.clone();
-            setup.maps.push(SeriesMap {
-                map: map.clone(),
-                picked_by: picked_by_team.clone(),
-                start_attack: None,
-                start_defense: None,
-            });
-            let mut resp = format!("<@&{}> picked `{}`. Maps remaining:\n", &picked_by_team.id, map.to_uppercase());
-            let map_str: String = setup.maps_remaining.iter().map(|map| format!("- `{}`\n", map.to_uppercase())).collect();
-            resp.push_str(map_str.as_str());
-            setup.current_step += 1;
-            if setup.current_step >= setup.veto_pick_order.len() {
-                setup.current_phase = SidePick;
-                resp = format!("<@&{}> picked `{}`. Map veto has concluded.\n\nTeams will now pick starting sides.\n", &picked_by_team.id, map.to_uppercase());
-                setup.current_step = 0;
-                resp.push_str(format!("It is <@&{}>'s turn to pick starting side for `{}`\nUse `/attack` or `/defense` to select starting side", setup.clone().team_two.unwrap().id, setup.maps[0].map.to_uppercase()).as_str());
-                return resp;
-            }
-            resp.push_str(format!("It is <@&{}>'s turn to `{}`", setup.veto_pick_order[setup.current_step].team.id, setup.veto_pick_order[setup.current_step].step_type.to_string()).as_str());
-            return resp;
+        let before = audit_snapshot(&data, guild_id);
+        let setup: &mut Setup = data.guild_mut::<Setup>(guild_id);
+        let map_index = setup.current_step;
+        let progress = apply_side_pick(setup, &user_role_partial, side)?;
+        let resp = format!("<@&{}> picked to start `{}` on `{}`", user_role_partial.id, side, setup.maps[map_index].map.to_uppercase());
+        if progress != VetoProgress::Completed {
+            let (_, next_pick) = current_turn(setup).unwrap();
+            let resp = format!("{}. It is now <@&{}>'s turn to pick starting side on `{}`", resp, next_pick.id, setup.maps[setup.current_step].map.to_uppercase());
+            queue_audit(&mut data, guild_id, msg, before);
+            return Ok(resp.into());
         }
-    }
-    String::from("There was an issue picking a map")
+        let finished_setup = setup.clone();
+        queue_audit(&mut data, guild_id, msg, before);
+        (resp, finished_setup)
+    };
+    finish_setup(context, msg).await?;
+    let colour = team_colour(context, &finished_setup.clone().team_one.unwrap()).await;
+    Ok(Response { content: resp, embeds: vec![eos_embed(finished_setup, colour)], components: None, ephemeral: false })
 }
 
-pub(crate) async fn handle_ban_option(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    if let Err(err) = is_phase_allowed(context, msg, MapVeto).await {
-        return err;
-    }
-    {
-        let data = context.data.write().await;
-        let setup: &Setup = data.get::<Setup>().unwrap();
-        if setup.veto_pick_order.get(setup.current_step).unwrap().step_type != Veto {
-            return String::from("It is not your turn to ban");
-        }
-    }
-    if let Ok(user_role_partial) = user_team(context, msg).await {
-        let mut data = context.data.write().await;
-        let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-        if setup.veto_pick_order.get(setup.current_step).unwrap().team.id != user_role_partial.id {
-            return String::from("It is not your turn to ban");
-        }
-        let option = msg.data
-            .options
-            .get(0)
-            .expect("Expected map name option")
-            .resolved
-            .as_ref()
-            .expect("Expected object");
-        if let ApplicationCommandInteractionDataOptionValue::String(map) = option {
-            if !setup.maps_remaining.contains(map) {
-                return String::from("Select a remaining map");
-            }
-            setup.veto_pick_order[setup.current_step].map = Some(String::from(map));
-            let map_index = setup.maps_remaining.iter().position(|m| m == map).unwrap();
-            setup.maps_remaining.remove(map_index);
-            let banned_by_team = setup.veto_pick_order[setup.current_step].team.clone();
-            let mut resp = format!("<@&{}> banned `{}`. Maps remaining:\n", &banned_by_team.id, map);
-            let map_str: String = setup.maps_remaining.iter().map(|map| format!("- `{}`\n", map.to_uppercase())).collect();
-            resp.push_str(map_str.as_str());
-            setup.current_step += 1;
-            if setup.current_step >= setup.veto_pick_order.len() {
-                setup.current_phase = SidePick;
-                setup.current_step = 0;
-                resp = String::from("Map veto has concluded. Teams will now pick starting sides. \n");
-                resp.push_str(format!("It is <@&{}>'s turn to pick starting side for `{}`\nUse `/attack` or `/defense` to select starting side", setup.clone().team_two.unwrap().id, setup.maps[0].map.to_uppercase()).as_str());
-                return resp;
-            }
-            resp.push_str(format!("It is <@&{}>'s turn to `{}`", setup.veto_pick_order[setup.current_step].team.id, setup.veto_pick_order[setup.current_step].step_type.to_string()).as_str());
-            return resp;
-        }
-    }
-    String::from("There was an issue banning a map")
+pub(crate) async fn handle_pick_option(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    handle_map_step(context, msg, Turn::Pick).await
 }
 
-pub(crate) async fn handle_map_list(context: &Context) -> String {
-    let data = context.data.write().await;
-    let maps: &Vec<String> = data.get::<Maps>().unwrap();
+pub(crate) async fn handle_ban_option(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    handle_map_step(context, msg, Turn::Ban).await
+}
+
+async fn handle_map_step(context: &Context, msg: &ApplicationCommandInteraction, turn: Turn) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    is_phase_allowed(context, msg, MapVeto).await?;
+    let user_role_partial = user_team(context, msg).await?;
+    let map_option = required_string(&msg.data.options, "map")?.trim();
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let setup: &mut Setup = data.guild_mut::<Setup>(guild_id);
+    // Maps are typed freely since the pool differs per guild, so match them case insensitively
+    let map = setup.maps_remaining.iter().find(|m| m.eq_ignore_ascii_case(map_option)).cloned().unwrap_or_else(|| map_option.to_string());
+    let progress = apply_map_step(setup, &user_role_partial, turn, &map)?;
+    let action = if turn == Turn::Ban { "banned" } else { "picked" };
+    let resp = if progress == VetoProgress::VetoFinished {
+        let mut resp = format!("<@&{}> {} `{}`. Map veto has concluded.\n\nTeams will now pick starting sides.\n", user_role_partial.id, action, map.to_uppercase());
+        resp.push_str(format!("It is <@&{}>'s turn to pick starting side for `{}`\nUse `/attack` or `/defense` to select starting side", side_picker(setup, 0).id, setup.maps[0].map.to_uppercase()).as_str());
+        resp
+    } else {
+        let mut resp = format!("<@&{}> {} `{}`. Maps remaining:\n", user_role_partial.id, action, map.to_uppercase());
+        let map_str: String = setup.maps_remaining.iter().map(|map| format!("- `{}`\n", map.to_uppercase())).collect();
+        resp.push_str(map_str.as_str());
+        let step = &setup.veto_pick_order[setup.current_step];
+        resp.push_str(format!("It is <@&{}>'s turn to `{}`", step.team.id, step.step_type).as_str());
+        resp
+    };
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_map_list(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let maps = get_maps(context, guild_id(msg)?).await;
     let map_str: String = maps.iter().map(|map| format!("- `{}`\n", map)).collect();
-    return MessageBuilder::new()
+    Ok(MessageBuilder::new()
         .push_line("Current map pool:")
         .push(map_str)
-        .build();
+        .build().into())
 }
 
-pub(crate) async fn handle_schedule(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let option_one = msg.data
-        .options
-        .get(0)
-        .expect("Expected date option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-    let option_two = msg.data
-        .options
-        .get(1)
-        .expect("Expected time option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-    let mut date: Option<NaiveDate> = None;
-    let mut match_date_str = String::from("");
-    let mut time: Option<String> = None;
-    if let ApplicationCommandInteractionDataOptionValue::String(date_str) = option_one {
-        match_date_str = String::from(date_str);
-        if let Ok(date_result) = NaiveDate::parse_from_str(date_str, "%m/%d/%Y") {
-            date = Some(date_result);
-        } else {
-            return String::from("Incorrect date format. Please use correct format (Month/Day/Year) i.e. `12/23/2022`");
+pub(crate) async fn handle_map_stats(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    let (setups, stats) = league_map_stats(data.guild::<Matches>(guild_id));
+    if setups == 0 {
+        return Err(CommandError::User(String::from("No match setups have been completed")));
+    }
+    Ok(print_map_stats(setups, &stats).into())
+}
+
+pub(crate) async fn handle_team_stats(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let team = required_role(&msg.data.options, "team")?;
+    let data = context.data.read().await;
+    let stats = team_map_stats(data.guild::<Matches>(guild_id), team.id);
+    if stats.maps.is_empty() {
+        return Err(CommandError::User(format!("<@&{}> has no completed setups or reported map scores", team.id)));
+    }
+    Ok(print_team_stats(&team, &stats).into())
+}
+
+pub(crate) async fn handle_head_to_head(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut teams = Vec::new();
+    for name in ["team1", "team2"] {
+        if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_role)) = find_option(&msg.data.options, name) {
+            teams.push(RolePartial { id: team_role.id, name: team_role.name.to_string(), guild_id: team_role.guild_id });
+        }
+    }
+    if teams.len() != 2 || teams[0].id == teams[1].id {
+        return Err(CommandError::User(String::from("Select two different teams")));
+    }
+    let data = context.data.read().await;
+    let meetings: Vec<&Match> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| teams.iter().all(|t| m.team_one.id == t.id || m.team_two.id == t.id))
+        .collect();
+    if meetings.is_empty() {
+        return Err(CommandError::User(format!("<@&{}> and <@&{}> have never been matched up", teams[0].id, teams[1].id)));
+    }
+    let series_wins = |team: &RolePartial| meetings.iter().filter(|m| m.result.as_ref().is_some_and(|r| r.winner.id == team.id)).count();
+    let map_wins = |team: &RolePartial| meetings.iter()
+        .filter_map(|m| m.result.as_ref().map(|r| (m, r)))
+        .flat_map(|(m, r)| r.map_scores.iter().map(move |s| if m.team_one.id == team.id { s.team_one_score > s.team_two_score } else { s.team_two_score > s.team_one_score }))
+        .filter(|won| *won)
+        .count();
+    let mut resp = format!("**{}** vs **{}** - {} meetings\nSeries: `{}-{}` Maps: `{}-{}`\n\n", teams[0].name, teams[1].name, meetings.len(),
+                           series_wins(&teams[0]), series_wins(&teams[1]), map_wins(&teams[0]), map_wins(&teams[1]));
+    for m in meetings.iter().rev() {
+        let mut meeting = String::new();
+        if let Some(season) = &m.season {
+            meeting.push_str(format!("_{}_\n", season).as_str());
+        }
+        meeting.push_str(print_match_info(m, false).as_str());
+        if m.setup_info.is_some() {
+            meeting.push_str(print_veto_info(m).as_str());
+            meeting.push('\n');
         }
+        if resp.len() + meeting.len() > 1900 {
+            resp.push_str("_Older meetings omitted_");
+            break;
+        }
+        resp.push_str(meeting.as_str());
     }
-    if let ApplicationCommandInteractionDataOptionValue::String(time_str) = option_two {
-        time = Some(time_str.to_string());
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_schedule(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let match_date_str = required_string(&msg.data.options, "date")?;
+    let time = required_string(&msg.data.options, "time")?;
+    let mut match_id = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(id)) = find_option(&msg.data.options, "matchid") {
+        match_id = Some(id.as_str());
     }
-    if let Ok(roles) = context.http.get_guild_roles(*msg.guild_id.unwrap().as_u64()).await {
+    let date = match NaiveDate::parse_from_str(match_date_str, "%m/%d/%Y") {
+        Ok(date) => date,
+        Err(_) => return Err(CommandError::User(String::from("Incorrect date format. Please use correct format (Month/Day/Year) i.e. `12/23/2022`"))),
+    };
+    if let Ok(roles) = context.http.get_guild_roles(*guild_id.as_u64()).await {
         let team_roles: Vec<Role> = roles.into_iter().filter(|r| r.name.starts_with("Team")).collect();
         let mut user_team_role: Option<Role> = None;
         for team_role in team_roles {
@@ -374,183 +332,1601 @@ pub(crate) async fn handle_schedule(context: &Context, msg: &ApplicationCommandI
         }
         if let Some(team_role) = user_team_role {
             let mut data = context.data.write().await;
-            let matches: &mut Vec<Match> = data.get_mut::<Matches>().unwrap();
-            let mut resp_str = String::new();
-            for m in matches.iter_mut() {
-                if m.team_one.id != team_role.id && m.team_two.id != team_role.id { continue; }
-                m.schedule_info = Some(ScheduleInfo { date: date.unwrap(), time_str: time.clone().unwrap() });
-                resp_str = format!("Your next match (<@&{}> vs <@&{}>) is scheduled for `{} @ {}`", m.team_one.id.as_u64(), m.team_two.id.as_u64(), &match_date_str, time.clone().unwrap());
-            }
-            write_to_file("matches.json", serde_json::to_string(matches).unwrap()).await;
-            if !resp_str.is_empty() {
-                return resp_str;
-            }
-            return String::from("Your team does not have any scheduled matches");
+            let before = audit_snapshot(&data, guild_id);
+            let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+            let next_match = matches.iter_mut()
+                .filter(|m| m.match_state != Completed && (m.team_one.id == team_role.id || m.team_two.id == team_role.id))
+                .find(|m| match_id.is_none_or(|id| is_match_id(m, id)));
+            let m = match (next_match, match_id) {
+                (Some(m), _) => m,
+                (None, Some(_)) => return Err(CommandError::User(String::from("Your team has no open match with that ID"))),
+                (None, None) => return Err(CommandError::User(String::from("Your team does not have any scheduled matches"))),
+            };
+            m.schedule_info = Some(ScheduleInfo { date, time_str: time.clone() });
+            m.match_state = MatchState::Scheduled;
+            let resp = format!("Match `{}` (<@&{}> vs <@&{}>) is scheduled for `{} @ {}`", m.short_id, m.team_one.id.as_u64(), m.team_two.id.as_u64(), match_date_str, time);
+            write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+            queue_audit(&mut data, guild_id, msg, before);
+            return Ok(resp.into());
         }
     }
-    String::from("You are not part of any team. Verify you have a role starting with `Team`")
+    Err(CommandError::User(String::from("You are not part of any team. Verify you have a role starting with `Team`")))
 }
 
-pub(crate) async fn handle_match(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let option_one = msg.data
-        .options
-        .get(0)
-        .expect("Expected match id")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-
-    if let ApplicationCommandInteractionDataOptionValue::String(match_id) = option_one {
-        let data = context.data.write().await;
-        let matches: &Vec<Match> = data.get::<Matches>().unwrap();
+pub(crate) async fn handle_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let find_match = {
+        let data = context.data.read().await;
+        let matches: &Vec<Match> = data.guild::<Matches>(guild_id);
         if matches.is_empty() {
-            return String::from("No matches have been added");
-        }
-        let id = Uuid::from_str(match_id);
-        if let Ok(uuid) = id {
-            let find_match = matches.iter().find(|m| m.id == uuid);
-            if let Some(m) = find_match {
-                let mut row = String::new();
-                row.push_str(print_match_info(m, false).as_str());
-                row.push_str(print_veto_info(m).as_str());
-                return row;
+            return Err(CommandError::User(String::from("No matches have been added")));
+        }
+        matches.iter().find(|m| is_match_id(m, match_id)).cloned()
+    };
+    let m = find_match.ok_or_else(|| CommandError::User(String::from("Could not find match")))?;
+    let colour = team_colour(context, &m.team_one).await;
+    let mut embeds = vec![match_embed(&m, colour)];
+    embeds.extend(veto_embed(&m));
+    Ok(Response { content: String::new(), embeds, components: None, ephemeral: false })
+}
+
+pub(crate) async fn handle_matches(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut show_ids = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(display)) = find_option(&msg.data.options, "displayid") {
+        show_ids = *display;
+    }
+    let mut states = vec![MatchState::Entered, MatchState::Scheduled];
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(true)) = find_option(&msg.data.options, "showcompleted") {
+        states = vec![Completed];
+    }
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(state)) = find_option(&msg.data.options, "state") {
+        states = match MatchState::from_str(state) {
+            Ok(state) => vec![state],
+            Err(_) => vec![MatchState::Entered, MatchState::Scheduled, Completed],
+        };
+    }
+    let mut dates = [None, None];
+    for (i, name) in ["from", "to"].iter().enumerate() {
+        if let Some(ApplicationCommandInteractionDataOptionValue::String(date_str)) = find_option(&msg.data.options, name) {
+            match NaiveDate::parse_from_str(date_str.trim(), "%m/%d/%Y") {
+                Ok(date) => dates[i] = Some(date),
+                Err(_) => return Err(CommandError::User(String::from("Invalid date, use the format MM/DD/YYYY"))),
             }
-        } else {
-            return String::from("Invalid match id format");
         }
     }
-    String::from("Discord API error")
+    let mut sort_by_added = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(sort)) = find_option(&msg.data.options, "sort") {
+        sort_by_added = sort == "added";
+    }
+    let mut division = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(&msg.data.options, "division") {
+        division = Some(division_name.to_lowercase());
+    }
+    let week = week_option(&msg.data.options, "week")?;
+    let mut team = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_role)) = find_option(&msg.data.options, "team") {
+        team = Some(team_role.id);
+    }
+    let mut my_team = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(my_team_option)) = find_option(&msg.data.options, "myteam") {
+        my_team = *my_team_option;
+    }
+    if my_team {
+        let roles = context.http.get_guild_roles(*guild_id.as_u64()).await.unwrap_or_default();
+        match find_user_team_role(roles, &msg.user, &context).await {
+            Ok(team_role) => team = Some(team_role.id),
+            Err(_) => return Err(CommandError::User(String::from("You are not part of any team. Verify you have a role starting with `Team`"))),
+        }
+    }
+    let mut data = context.data.write().await;
+    if data.guild::<Matches>(guild_id).is_empty() {
+        return Err(CommandError::User(String::from("No matches have been added")));
+    }
+    let seasons: &Vec<Season> = data.guild::<Seasons>(guild_id);
+    let mut season = current_season(seasons).map(|s| Some(s.name.to_lowercase()));
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(season_name)) = find_option(&msg.data.options, "season") {
+        season = if season_name.trim().eq_ignore_ascii_case("none") { Some(None) } else { Some(Some(season_name.to_lowercase())) };
+    }
+    let filter = MatchFilter { show_ids, states, season, division, week, team, from: dates[0], to: dates[1], sort_by_added };
+    let key = *msg.id.as_u64();
+    let mut response = matches_page(data.guild::<Matches>(guild_id), &filter, key, 0)?;
+    response.ephemeral = my_team;
+    if response.components.is_some() {
+        let pages = data.get_mut::<MatchPages>().unwrap();
+        // Interaction ids increase over time, so the smallest key is the oldest listing
+        if pages.len() >= MAX_MATCH_PAGES {
+            if let Some(oldest) = pages.keys().min().cloned() { pages.remove(&oldest); }
+        }
+        pages.insert(key, filter);
+    }
+    Ok(response)
 }
 
-pub(crate) async fn handle_matches(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let option_one = msg.data
-        .options
-        .get(0);
-    let option_two = msg.data
-        .options
-        .get(1);
-    let data = context.data.write().await;
-    let matches: &Vec<Match> = data.get::<Matches>().unwrap();
-    if matches.is_empty() {
-        return String::from("No matches have been added");
+fn matches_page(matches: &[Match], filter: &MatchFilter, key: u64, page: usize) -> CommandResult {
+    let filtered = filter_matches(matches, filter);
+    if filtered.is_empty() {
+        return Err(CommandError::User(String::from("No matches found")));
     }
-    let mut show_completed = false;
-    if let Some(option) = option_two {
-        if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(display)) = &option.resolved {
-            show_completed = *display;
+    let pages = filtered.len().div_ceil(MATCHES_PER_PAGE);
+    let page = page.min(pages - 1);
+    let embed = matches_page_embed(&filtered[page * MATCHES_PER_PAGE..((page + 1) * MATCHES_PER_PAGE).min(filtered.len())], page, pages, filter.show_ids);
+    Ok(Response {
+        content: String::new(),
+        embeds: vec![embed],
+        components: if pages > 1 { Some(page_buttons(key, page, pages)) } else { None },
+        ephemeral: false,
+    })
+}
+
+pub(crate) async fn handle_matches_page(context: &Context, component: &MessageComponentInteraction) {
+    let mut parts = component.data.custom_id.split(':').skip(1).map(|p| p.parse::<u64>().ok());
+    let (key, page) = match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(key), Some(page)) => (key, page as usize),
+        _ => return,
+    };
+    let guild_id = match component.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let response = {
+        let data = context.data.read().await;
+        match data.get::<MatchPages>().unwrap().get(&key) {
+            Some(filter) => matches_page(data.guild::<Matches>(guild_id), filter, key, page)
+                .unwrap_or_else(|err| err.to_string().into()),
+            None => String::from("This match list has expired. Run `/matches` again").into(),
         }
+    };
+    let update = component.create_interaction_response(&context.http, |r| {
+        r.kind(InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|message| {
+                message.content(response.content).embeds(response.embeds).set_components(response.components.unwrap_or_default())
+            })
+    }).await;
+    if let Err(why) = update {
+        eprintln!("Cannot update match list: {}", why);
     }
-    let matches_str: String = matches.iter()
-        .filter(|m| if show_completed {
-            m.match_state == Completed
-        } else {
-            m.match_state != Completed
-        })
-        .map(|m| {
-            let mut row = String::new();
-            let mut show_ids = false;
-            if let Some(option) = option_one {
-                if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(display)) = &option.resolved {
-                    show_ids = *display;
-                }
-            }
-            row.push_str(print_match_info(m, show_ids).as_str());
-            row
-        })
-        .collect();
-    matches_str
 }
 
-pub(crate) async fn handle_add_match(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let admin_check = admin_check(context, msg).await;
-    if let Err(error) = admin_check { return error; }
-    let option_one = msg.data
-        .options
-        .get(0)
-        .expect("Expected teamone option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-    let option_two = msg.data
-        .options
-        .get(1)
-        .expect("Expected teamtwo option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-    let option_three = msg.data
-        .options
-        .get(2);
-    let mut team_one = None;
-    let mut team_two = None;
-    if let ApplicationCommandInteractionDataOptionValue::Role(team_one_role) = option_one {
-        team_one = Some(RolePartial { id: team_one_role.id, name: team_one_role.name.to_string(), guild_id: team_one_role.guild_id });
+pub(crate) async fn handle_add_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let team_one = required_role(&msg.data.options, "teamone")?;
+    let team_two = required_role(&msg.data.options, "teamtwo")?;
+    let option_three = find_option(&msg.data.options, "note");
+    let mut division = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(&msg.data.options, "division") {
+        division = Some(division_name.clone());
     }
-    if let ApplicationCommandInteractionDataOptionValue::Role(team_two_role) = option_two {
-        team_two = Some(RolePartial { id: team_two_role.id, name: team_two_role.name.to_string(), guild_id: team_two_role.guild_id });
+    let week = week_option(&msg.data.options, "week")?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let seasons: &Vec<Season> = data.guild::<Seasons>(guild_id);
+    let season = current_season(seasons).cloned();
+    if let Some(division_name) = &division {
+        match season.as_ref().and_then(|s| find_division(s, division_name)) {
+            Some(d) => division = Some(d.name.clone()),
+            None => return Err(CommandError::User(format!("Division `{}` does not exist in the current season", division_name))),
+        }
     }
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
     let mut new_match = Match {
         id: Uuid::new_v4(),
-        team_one: team_one.unwrap(),
-        team_two: team_two.unwrap(),
+        short_id: next_short_id(&mut last_id),
+        team_one,
+        team_two,
         note: None,
         date_added: Utc::now(),
         match_state: MatchState::Entered,
         schedule_info: None,
         setup_info: None,
+        result: None,
+        series_type: None,
+        season: season.map(|s| s.name),
+        division,
+        week,
+        thread_id: None,
+        lobby: None,
+        casters: Vec::new(),
+        disputes: Vec::new(),
+    };
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(option_value)) = option_three {
+        new_match.note = Option::from(option_value.clone());
+    }
+    let resp = format!("Successfully added new match `{}`", new_match.short_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    matches.push(new_match);
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_delete_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let deleted_match = match matches.iter().position(|m| is_match_id(m, match_id)) {
+        Some(index) => matches.remove(index),
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    let short_id = deleted_match.short_id.clone();
+    let lobby_match = deleted_match.lobby.as_ref().map(|_| deleted_match.clone());
+    let deleted: &mut Vec<DeletedMatch> = data.guild_mut::<DeletedMatches>(guild_id);
+    deleted.push(DeletedMatch { deleted_match, deleted_by: msg.user.id, date_deleted: Utc::now() });
+    write_to_file(&guild_file(guild_id, "deleted_matches.json"), serde_json::to_string_pretty(deleted)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    if let Some(m) = lobby_match {
+        let decided = m.result.as_ref().map(|r| r.date_set).unwrap_or_else(Utc::now);
+        schedule_lobby_cleanup(context, guild_id, &m, decided).await;
+    }
+    Ok(format!("Successfully deleted match `{}`. Use `/restorematch` to bring it back", short_id).into())
+}
+
+pub(crate) async fn handle_restore_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let deleted: &mut Vec<DeletedMatch> = data.guild_mut::<DeletedMatches>(guild_id);
+    let mut restored = match deleted.iter().position(|d| is_match_id(&d.deleted_match, match_id)) {
+        Some(index) => deleted.remove(index).deleted_match,
+        None => return Err(CommandError::User(String::from("Could not find a deleted match with that ID"))),
+    };
+    write_to_file(&guild_file(guild_id, "deleted_matches.json"), serde_json::to_string_pretty(deleted)?).await?;
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let mut resp = format!("Restored match `{}`", restored.short_id);
+    // IDs are no longer reused, but matches deleted before the counter existed may collide.
+    let renamed = matches.iter().any(|m| m.short_id == restored.short_id);
+    if renamed {
+        restored.short_id = next_short_id(&mut last_id);
+        resp.push_str(format!(" as `{}` since its ID has been reused", restored.short_id).as_str());
+    }
+    resp.push('\n');
+    resp.push_str(print_match_info(&restored, false).as_str());
+    matches.push(restored);
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    if renamed {
+        save_match_counter(&mut data, guild_id, last_id).await?;
+    }
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_purge_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let deleted: &mut Vec<DeletedMatch> = data.guild_mut::<DeletedMatches>(guild_id);
+    let purged = match deleted.iter().position(|d| is_match_id(&d.deleted_match, match_id)) {
+        Some(index) => deleted.remove(index).deleted_match,
+        None => return Err(CommandError::User(String::from("Could not find a deleted match with that ID. Only deleted matches can be purged"))),
+    };
+    write_to_file(&guild_file(guild_id, "deleted_matches.json"), serde_json::to_string_pretty(deleted)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(format!("Permanently removed match `{}` {} vs {}", purged.short_id, purged.team_one.name, purged.team_two.name).into())
+}
+
+pub(crate) async fn handle_cancel(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let maps = get_maps(context, guild_id).await;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let draft: &mut Setup = data.guild_mut::<Setup>(guild_id);
+    if draft.current_phase == Idle {
+        return Err(CommandError::User(String::from(" command only valid during `/setup` process")));
+    }
+    reset_setup(draft, maps);
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(String::from("`/setup` process cancelled.").into())
+}
+
+
+pub(crate) async fn handle_forfeit(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let mut match_id = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(id)) = find_option(&msg.data.options, "matchid") {
+        match_id = id.clone();
+    }
+    let mut forfeit_team = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_role)) = find_option(&msg.data.options, "team") {
+        forfeit_team = Some(team_role.id);
+    }
+    let mut reason = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(reason_str)) = find_option(&msg.data.options, "reason") {
+        reason = Some(reason_str.clone());
+    }
+    let mut result_type = ResultType::Forfeit;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(true)) = find_option(&msg.data.options, "noshow") {
+        result_type = ResultType::NoShow;
+    }
+    let maps = get_maps(context, guild_id).await;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let match_index = match matches.iter().position(|m| is_match_id(m, &match_id)) {
+        Some(index) => index,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    let m = matches[match_index].clone();
+    if m.result.is_some() {
+        return Err(CommandError::User(String::from("This match already has a result. Use `/admin setresult` to override it")));
+    }
+    let winner = if Some(m.team_one.id) == forfeit_team {
+        m.team_two.clone()
+    } else if Some(m.team_two.id) == forfeit_team {
+        m.team_one.clone()
+    } else {
+        return Err(CommandError::User(String::from("That team is not part of this match")));
+    };
+    let bracket_resp = update_brackets(&mut data, guild_id, m.id, &winner).await?;
+    let mut resp = format!("<@&{}> {} recorded. <@&{}> is awarded the win.", forfeit_team.unwrap(), result_type.to_string().to_lowercase(), winner.id);
+    resp.push_str(bracket_resp.as_str());
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    matches[match_index].result = Some(MatchResult { winner, result_type, map_scores: Vec::new(), reason, set_by: msg.user.id, date_set: Utc::now() });
+    matches[match_index].match_state = Completed;
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    let setup: &mut Setup = data.guild_mut::<Setup>(guild_id);
+    if setup.match_id == Some(m.id) {
+        reset_setup(setup, maps);
+    }
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    close_match_thread(context, m.thread_id, &resp).await;
+    schedule_lobby_cleanup(context, guild_id, &m, Utc::now()).await;
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_cast(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let mut stream = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(link)) = find_option(&msg.data.options, "stream") {
+        stream = Some(link.trim().to_string());
+    }
+    let mut drop_cast = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(drop_option)) = find_option(&msg.data.options, "drop") {
+        drop_cast = *drop_option;
+    }
+    let cast = context.data.read().await.get::<Config>().unwrap().guild(guild_id).cast;
+    let caster_role_id = cast.caster_role_id.ok_or_else(|| CommandError::User(String::from("Casting is not set up on this server")))?;
+    if !msg.user.has_role(&context.http, guild_id, caster_role_id).await.unwrap_or(false) {
+        return Err(CommandError::User(format!("Only members of <@&{}> can cast matches", caster_role_id)));
+    }
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let m = match matches.iter_mut().find(|m| is_match_id(m, match_id)) {
+        Some(m) => m,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
     };
-    if let Some(option) = option_three {
-        if let Some(ApplicationCommandInteractionDataOptionValue::String(option_value)) = &option.resolved {
-            new_match.note = Option::from(option_value.clone());
+    if m.result.is_some() {
+        return Err(CommandError::User(String::from("This match has already been decided")));
+    }
+    let schedule = match &m.schedule_info {
+        Some(schedule) => format!("{} @ {}", schedule.date.format("%m/%d/%Y"), schedule.time_str),
+        None => return Err(CommandError::User(String::from("Only scheduled matches can be cast"))),
+    };
+    let position = m.casters.iter().position(|c| c.user_id == msg.user.id);
+    let lobby = m.lobby.clone();
+    let resp = match (position, drop_cast) {
+        (Some(index), true) => {
+            m.casters.remove(index);
+            format!("<@&{}> <@&{}> <@{}> will no longer cast your match `{}`", m.team_one.id, m.team_two.id, msg.user.id, m.short_id)
+        }
+        (None, true) => return Err(CommandError::User(String::from("You are not casting this match"))),
+        (Some(index), false) => {
+            m.casters[index].stream = stream.or_else(|| m.casters[index].stream.take());
+            format!("Updated your cast of `{}`\n{}", m.short_id, print_match_info(m, false))
+        }
+        (None, false) => {
+            if m.casters.len() >= cast.max_casters {
+                return Err(CommandError::User(format!("This match already has {} casters", m.casters.len())));
+            }
+            m.casters.push(Caster { user_id: msg.user.id, stream });
+            format!("<@&{}> <@&{}> your match `{}` on `{}` will be cast\n{}", m.team_one.id, m.team_two.id, m.short_id, schedule, print_match_info(m, false))
+        }
+    };
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    // Joining or dropping changes who can see the lobby, updating the stream link does not
+    if let Some(lobby) = lobby.filter(|_| position.is_none() || drop_cast) {
+        set_lobby_caster(context, &lobby, msg.user.id, !drop_cast).await;
+    }
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_broadcasts(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    Ok(broadcast_schedule(data.guild::<Matches>(guild_id), Utc::now().date_naive()).into())
+}
+
+pub(crate) async fn handle_dispute(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?;
+    let reason = required_string(&msg.data.options, "reason")?.trim().to_string();
+    let m = {
+        let data = context.data.read().await;
+        match data.guild::<Matches>(guild_id).iter().find(|m| is_match_id(m, match_id)) {
+            Some(m) => m.clone(),
+            None => return Err(CommandError::User(String::from("Could not find match"))),
+        }
+    };
+    let team = if msg.user.has_role(&context.http, guild_id, m.team_one.id).await.unwrap_or(false) {
+        m.team_one.clone()
+    } else if msg.user.has_role(&context.http, guild_id, m.team_two.id).await.unwrap_or(false) {
+        m.team_two.clone()
+    } else {
+        return Err(CommandError::User(String::from("Only members of the two teams can dispute this match")));
+    };
+    if m.disputes.iter().any(|d| d.resolution.is_none()) {
+        return Err(CommandError::User(String::from("This match already has an open dispute")));
+    }
+    let admin_role_id = context.data.read().await.get::<Config>().unwrap().guild(guild_id).admin_role_id;
+    let parent = thread_parent(context, msg.channel_id).await;
+    let thread = parent.create_private_thread(&context.http, |t| {
+        t.name(format!("Dispute {} {} vs {}", m.short_id, m.team_one.name, m.team_two.name)).auto_archive_duration(1440)
+    }).await?;
+    let mut roles = vec![m.team_one.id, m.team_two.id];
+    roles.extend(admin_role_id.map(RoleId));
+    // Mentioning a role does not add its members to a private thread, so both teams and the admins are added one by one
+    thread.id.add_thread_member(&context.http, msg.user.id).await?;
+    let mut members = Box::pin(guild_id.members_iter(&context.http));
+    while let Some(member) = members.next().await {
+        let member = member?;
+        if member.user.id != msg.user.id && member.roles.iter().any(|role| roles.contains(role)) {
+            thread.id.add_thread_member(&context.http, member.user.id).await?;
         }
     }
+    let mentions: String = roles.iter().map(|id| format!("<@&{}> ", id)).collect();
+    thread.id.send_message(&context.http, |message| {
+        message.content(format!("{}\n<@{}> of <@&{}> disputes match `{}`: _{}_\nResults for this match cannot be reported until an admin resolves the dispute.", mentions.trim_end(), msg.user.id, team.id, m.short_id, reason))
+            .allowed_mentions(|allowed| allowed.roles(roles.clone()))
+    }).await?;
+
     let mut data = context.data.write().await;
-    let matches: &mut Vec<Match> = data.get_mut::<Matches>().unwrap();
-    matches.push(new_match);
-    write_to_file("matches.json", serde_json::to_string_pretty(matches).unwrap()).await;
-    String::from("Successfully added new match")
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    if let Some(disputed) = matches.iter_mut().find(|d| d.id == m.id) {
+        disputed.disputes.push(Dispute { opened_by: msg.user.id, team, reason, date_opened: Utc::now(), thread_id: Some(thread.id), resolution: None });
+    }
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(Response::ephemeral(format!("Dispute opened in <#{}>", thread.id)))
+}
+
+pub(crate) async fn handle_audit(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(&msg.data.options, "matchid")?.trim();
+    let id = {
+        let data = context.data.read().await;
+        data.guild::<Matches>(guild_id).iter().find(|m| is_match_id(m, match_id)).map(|m| m.id)
+    };
+    let entries = read_audit(guild_id);
+    let history: Vec<&AuditEntry> = entries.iter()
+        .filter(|e| (id.is_some() && e.match_id == id)
+            || e.short_id.as_ref().is_some_and(|s| s.eq_ignore_ascii_case(match_id))
+            || e.match_id.is_some_and(|m| m.to_string() == match_id))
+        .collect();
+    if history.is_empty() {
+        return Err(CommandError::User(format!("No changes have been recorded for `{}`", match_id)));
+    }
+    let mut lines: Vec<String> = Vec::new();
+    let mut length = 0;
+    for entry in history.iter().rev() {
+        let line = format!("{}\n", print_audit_entry(entry));
+        length += line.len();
+        if length > 1800 { break; }
+        lines.push(line);
+    }
+    let mut resp = format!("**Change history of `{}`** - latest {} of {} changes\n", match_id, lines.len(), history.len());
+    resp.extend(lines.into_iter().rev());
+    Ok(Response::ephemeral(resp))
+}
+
+pub(crate) async fn handle_report(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut match_id = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(id)) = find_option(&msg.data.options, "matchid") {
+        match_id = id.clone();
+    }
+    let mut scores = Vec::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(scores_str)) = find_option(&msg.data.options, "scores") {
+        for score in scores_str.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()) {
+            let parsed: Vec<u32> = score.split('-').filter_map(|n| n.trim().parse().ok()).collect();
+            if parsed.len() != 2 {
+                return Err(CommandError::User(String::from("Incorrect score format. Please list map scores from team 1's view i.e. `13-7 9-13 13-11`")));
+            }
+            scores.push((parsed[0], parsed[1]));
+        }
+    }
+    if scores.is_empty() || scores.iter().any(|(one, two)| one == two) {
+        return Err(CommandError::User(String::from("Every map needs a winner. Please list map scores from team 1's view i.e. `13-7 9-13 13-11`")));
+    }
+    let m = {
+        let data = context.data.read().await;
+        let matches: &Vec<Match> = data.guild::<Matches>(guild_id);
+        match matches.iter().find(|m| is_match_id(m, &match_id)) {
+            Some(m) => m.clone(),
+            None => return Err(CommandError::User(String::from("Could not find match"))),
+        }
+    };
+    if m.result.is_some() {
+        return Err(CommandError::User(String::from("This match already has a result")));
+    }
+    if m.disputes.iter().any(|d| d.resolution.is_none()) {
+        return Err(CommandError::User(String::from("This match has an open dispute. Results can be reported once an admin resolves it")));
+    }
+    if admin_check(context, msg).await.is_err() {
+        let in_team_one = msg.user.has_role(&context.http, m.team_one.guild_id, m.team_one.id).await.unwrap_or(false);
+        let in_team_two = msg.user.has_role(&context.http, m.team_two.guild_id, m.team_two.id).await.unwrap_or(false);
+        if !in_team_one && !in_team_two {
+            return Err(CommandError::User(String::from("Only members of the two teams can report this match")));
+        }
+    }
+    let series_maps: Vec<String> = m.setup_info.as_ref().map(|s| s.maps.iter().map(|map| map.map.clone()).collect()).unwrap_or_default();
+    if !series_maps.is_empty() && scores.len() > series_maps.len() {
+        return Err(CommandError::User(format!("This series only has {} maps", series_maps.len())));
+    }
+    let map_scores: Vec<MapScore> = scores.iter().enumerate()
+        .map(|(i, (one, two))| MapScore {
+            map: series_maps.get(i).cloned().unwrap_or(format!("Map {}", i + 1)),
+            team_one_score: *one,
+            team_two_score: *two,
+        })
+        .collect();
+    let team_one_maps = map_scores.iter().filter(|s| s.team_one_score > s.team_two_score).count();
+    let team_two_maps = map_scores.len() - team_one_maps;
+    if team_one_maps == team_two_maps {
+        return Err(CommandError::User(String::from("The reported maps are tied. Report every map played")));
+    }
+    let length = m.series_type.as_ref().map(series_length).or(if series_maps.is_empty() { None } else { Some(series_maps.len()) });
+    if let Some(length) = length {
+        let needed = length / 2 + 1;
+        let mut wins = (0, 0);
+        let decided_after = map_scores.iter().position(|s| {
+            if s.team_one_score > s.team_two_score { wins.0 += 1 } else { wins.1 += 1 }
+            wins.0 == needed || wins.1 == needed
+        });
+        match decided_after {
+            Some(index) if index + 1 == map_scores.len() => {}
+            Some(index) => return Err(CommandError::User(format!("This best of {} was decided after {} maps. Only report the maps that were played", length, index + 1))),
+            None => return Err(CommandError::User(format!("A best of {} is won with {} maps. Report every map played", length, needed))),
+        }
+    }
+    let winner = if team_one_maps > team_two_maps { m.team_one.clone() } else { m.team_two.clone() };
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let bracket_resp = update_brackets(&mut data, guild_id, m.id, &winner).await?;
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let reported = match matches.iter_mut().find(|r| r.id == m.id) {
+        Some(reported) => reported,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    reported.result = Some(MatchResult { winner: winner.clone(), result_type: ResultType::Played, map_scores, reason: None, set_by: msg.user.id, date_set: Utc::now() });
+    reported.match_state = Completed;
+    let mut resp = format!("Result reported: <@&{}> wins `{}-{}`\n", winner.id, team_one_maps.max(team_two_maps), team_one_maps.min(team_two_maps));
+    resp.push_str(print_match_info(reported, false).as_str());
+    resp.push_str(bracket_resp.as_str());
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    close_match_thread(context, m.thread_id, &resp).await;
+    schedule_lobby_cleanup(context, guild_id, &m, Utc::now()).await;
+    Ok(resp.into())
 }
 
-pub(crate) async fn handle_delete_match(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let admin_check = admin_check(context, msg).await;
-    if let Err(error) = admin_check { return error; }
-    let option_one = msg.data
+pub(crate) async fn handle_admin(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let subcommand = msg.data
         .options
-        .get(0)
-        .expect("Expected matchid option")
-        .resolved
-        .as_ref()
-        .expect("Expected object");
-    let mut parsed_match_id: Option<Uuid> = None;
-    if let ApplicationCommandInteractionDataOptionValue::String(match_id) = option_one {
-        if let Ok(id) = Uuid::from_str(match_id) {
-            parsed_match_id = Some(id);
+        .first()
+        .ok_or_else(|| CommandError::MissingOption(String::from("subcommand")))?;
+    match subcommand.name.as_str() {
+        "setresult" => handle_set_result(context, msg, &subcommand.options).await,
+        "resolvedispute" => handle_resolve_dispute(context, msg, &subcommand.options).await,
+        "newseason" => handle_new_season(context, msg, &subcommand.options).await,
+        "adddivision" => handle_add_division(context, msg, &subcommand.options).await,
+        "addteam" => handle_add_division_team(context, msg, &subcommand.options).await,
+        "seed" => handle_seed(context, msg, &subcommand.options).await,
+        "roundrobin" => handle_round_robin(context, guild_id, &subcommand.options).await,
+        "confirmschedule" => handle_confirm_schedule(context, msg).await,
+        "bracket" => handle_create_bracket(context, msg, &subcommand.options).await,
+        "swiss" => handle_create_swiss(context, msg, &subcommand.options).await,
+        "swissnext" => handle_next_swiss_round(context, msg, &subcommand.options).await,
+        _ => Err(CommandError::UnknownCommand(subcommand.name.clone())),
+    }
+}
+
+async fn handle_set_result(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut match_id = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(id)) = find_option(options, "matchid") {
+        match_id = id.clone();
+    }
+    let mut winner_team = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_role)) = find_option(options, "winner") {
+        winner_team = Some(team_role.id);
+    }
+    let mut reason = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(reason_str)) = find_option(options, "reason") {
+        reason = Some(reason_str.clone());
+    }
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let match_index = match matches.iter().position(|m| is_match_id(m, &match_id)) {
+        Some(index) => index,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    let m = matches[match_index].clone();
+    let winner = if Some(m.team_one.id) == winner_team {
+        m.team_one.clone()
+    } else if Some(m.team_two.id) == winner_team {
+        m.team_two.clone()
+    } else {
+        return Err(CommandError::User(String::from("That team is not part of this match")));
+    };
+    let bracket_resp = update_brackets(&mut data, guild_id, m.id, &winner).await?;
+    let mut resp = format!("Result set: <@&{}> wins {} vs {}", winner.id, m.team_one.name, m.team_two.name);
+    resp.push_str(bracket_resp.as_str());
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    matches[match_index].result = Some(MatchResult { winner, result_type: ResultType::AdminOverride, map_scores: Vec::new(), reason, set_by: msg.user.id, date_set: Utc::now() });
+    matches[match_index].match_state = Completed;
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    close_match_thread(context, m.thread_id, &resp).await;
+    schedule_lobby_cleanup(context, guild_id, &m, Utc::now()).await;
+    Ok(resp.into())
+}
+
+async fn handle_resolve_dispute(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let match_id = required_string(options, "matchid")?;
+    let outcome = match required_string(options, "outcome")?.as_str() {
+        "upheld" => DisputeOutcome::Upheld,
+        _ => DisputeOutcome::Rejected,
+    };
+    let decision = required_string(options, "decision")?.trim().to_string();
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let m = match matches.iter_mut().find(|m| is_match_id(m, match_id)) {
+        Some(m) => m,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    let dispute = match m.disputes.iter_mut().find(|d| d.resolution.is_none()) {
+        Some(dispute) => dispute,
+        None => return Err(CommandError::User(String::from("This match has no open dispute"))),
+    };
+    let resp = format!("Dispute on `{}` by <@&{}> {}: _{}_", m.short_id, dispute.team.id, outcome.to_string().to_lowercase(), decision);
+    dispute.resolution = Some(DisputeResolution { outcome, decision, resolved_by: msg.user.id, date_resolved: Utc::now() });
+    let thread_id = dispute.thread_id;
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    drop(data);
+    close_match_thread(context, thread_id, &resp).await;
+    Ok(resp.into())
+}
+
+async fn handle_create_bracket(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(bracket_name)) = find_option(options, "name") {
+        name = bracket_name.trim().to_string();
+    }
+    let mut double = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(format)) = find_option(options, "format") {
+        double = format == "double";
+    }
+    let mut team_count = 0;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Integer(count)) = find_option(options, "teams") {
+        team_count = (*count).max(0) as usize;
+    }
+    let mut division = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(options, "division") {
+        division = Some(division_name.to_lowercase());
+    }
+    let mut series_type = Bo3;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(type_str)) = find_option(options, "type") {
+        series_type = SeriesType::from_str(type_str).unwrap_or(Bo3);
+    }
+    let mut final_type = Bo5;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(type_str)) = find_option(options, "finaltype") {
+        final_type = SeriesType::from_str(type_str).unwrap_or(Bo5);
+    }
+    let mut third_place = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(third_place_option)) = find_option(options, "thirdplace") {
+        third_place = *third_place_option;
+    }
+    if team_count < if double { 3 } else { 2 } {
+        return Err(CommandError::User(String::from("A single elimination bracket needs at least 2 teams and a double elimination bracket at least 3")));
+    }
+    if third_place && (double || team_count < 4) {
+        return Err(CommandError::User(String::from("A third place match needs a single elimination bracket with at least 4 teams")));
+    }
+    let mut data = context.data.write().await;
+    if data.guild::<Brackets>(guild_id).iter().any(|b| b.name.eq_ignore_ascii_case(&name)) {
+        return Err(CommandError::User(format!("Bracket `{}` already exists", name)));
+    }
+    let season = current_season(data.guild::<Seasons>(guild_id)).map(|s| s.name.clone());
+    let season_matches: Vec<Match> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| season.is_none() || m.season == season)
+        .filter(|m| division.is_none() || m.division.as_ref().map(|d| d.to_lowercase()) == division)
+        .cloned()
+        .collect();
+    let seeds: Vec<RolePartial> = team_seeds(current_season(data.guild::<Seasons>(guild_id)), &season_matches).into_iter().take(team_count).collect();
+    if seeds.len() < team_count {
+        return Err(CommandError::User(format!("Only {} teams are seeded", seeds.len())));
+    }
+    let higher_seed_home = data.get::<Config>().unwrap().guild(guild_id).seeding.unwrap_or_default().home_slot;
+    let mut bracket = if double {
+        build_double_elimination(name.clone(), season, &seeds, &series_type, &final_type)
+    } else {
+        build_single_elimination(name.clone(), season, &seeds, &series_type, &final_type, third_place)
+    };
+    let before = audit_snapshot(&data, guild_id);
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let created = create_bracket_matches(&mut bracket, matches, &mut last_id, higher_seed_home);
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    let brackets: &mut Vec<Bracket> = data.guild_mut::<Brackets>(guild_id);
+    brackets.push(bracket);
+    write_to_file(&guild_file(guild_id, "brackets.json"), serde_json::to_string_pretty(brackets)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    let mut resp = format!("Bracket `{}` created. Seeds:\n", name);
+    for (i, team) in seeds.iter().enumerate() {
+        resp.push_str(format!("{}. {}\n", i + 1, team.name).as_str());
+    }
+    resp.push_str("\nFirst matches:\n");
+    for m in created {
+        resp.push_str(print_match_info(&m, false).as_str());
+    }
+    Ok(resp.into())
+}
+
+async fn handle_new_season(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(season_name)) = find_option(options, "name") {
+        name = season_name.trim().to_string();
+    }
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let seasons: &mut Vec<Season> = data.guild_mut::<Seasons>(guild_id);
+    if seasons.iter().any(|s| s.name.eq_ignore_ascii_case(&name)) {
+        return Err(CommandError::User(format!("Season `{}` already exists", name)));
+    }
+    let mut resp = String::new();
+    if let Some(previous) = seasons.iter_mut().find(|s| s.date_archived.is_none()) {
+        previous.date_archived = Some(Utc::now());
+        resp.push_str(format!("Season `{}` has been archived. ", previous.name).as_str());
+    }
+    seasons.push(Season { name: name.clone(), date_started: Utc::now(), date_archived: None, divisions: Vec::new(), seeds: Vec::new() });
+    write_to_file(&guild_file(guild_id, "seasons.json"), serde_json::to_string_pretty(seasons)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    resp.push_str(format!("Season `{}` has started", name).as_str());
+    Ok(resp.into())
+}
+
+async fn handle_add_division(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(options, "name") {
+        name = division_name.trim().to_string();
+    }
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let seasons: &mut Vec<Season> = data.guild_mut::<Seasons>(guild_id);
+    let season = match seasons.iter_mut().find(|s| s.date_archived.is_none()) {
+        Some(s) => s,
+        None => return Err(CommandError::User(String::from("There is no current season. Start one with `/admin newseason`"))),
+    };
+    if find_division(season, &name).is_some() {
+        return Err(CommandError::User(format!("Division `{}` already exists", name)));
+    }
+    season.divisions.push(Division { name: name.clone(), teams: Vec::new() });
+    let resp = format!("Division `{}` added to season `{}`", name, season.name);
+    write_to_file(&guild_file(guild_id, "seasons.json"), serde_json::to_string_pretty(seasons)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+async fn handle_add_division_team(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(options, "division") {
+        name = division_name.trim().to_string();
+    }
+    let team = required_role(options, "team")?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let seasons: &mut Vec<Season> = data.guild_mut::<Seasons>(guild_id);
+    let season = match seasons.iter_mut().find(|s| s.date_archived.is_none()) {
+        Some(s) => s,
+        None => return Err(CommandError::User(String::from("There is no current season. Start one with `/admin newseason`"))),
+    };
+    if season.divisions.iter().any(|d| d.teams.iter().any(|t| t.id == team.id)) {
+        return Err(CommandError::User(format!("<@&{}> is already in a division this season", team.id)));
+    }
+    let division = match season.divisions.iter_mut().find(|d| d.name.eq_ignore_ascii_case(&name)) {
+        Some(d) => d,
+        None => return Err(CommandError::User(format!("Division `{}` does not exist in the current season", name))),
+    };
+    let resp = format!("<@&{}> added to division `{}`", team.id, division.name);
+    division.teams.push(team);
+    write_to_file(&guild_file(guild_id, "seasons.json"), serde_json::to_string_pretty(seasons)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+async fn handle_seed(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let team = required_role(options, "team")?;
+    let mut seed = 0;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Integer(seed_num)) = find_option(options, "seed") {
+        seed = (*seed_num).max(0) as usize;
+    }
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let seasons: &mut Vec<Season> = data.guild_mut::<Seasons>(guild_id);
+    let season = match seasons.iter_mut().find(|s| s.date_archived.is_none()) {
+        Some(s) => s,
+        None => return Err(CommandError::User(String::from("There is no current season. Start one with `/admin newseason`"))),
+    };
+    season.seeds.retain(|t| t.id != team.id);
+    let resp = if seed == 0 {
+        format!("Removed the manual seed of <@&{}>", team.id)
+    } else {
+        let index = (seed - 1).min(season.seeds.len());
+        season.seeds.insert(index, team.clone());
+        format!("<@&{}> is now seed {}", team.id, index + 1)
+    };
+    write_to_file(&guild_file(guild_id, "seasons.json"), serde_json::to_string_pretty(seasons)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_seeds(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    let season = current_season(data.guild::<Seasons>(guild_id));
+    let season_name = season.map(|s| s.name.clone());
+    let season_matches: Vec<Match> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| season_name.is_none() || m.season == season_name)
+        .cloned()
+        .collect();
+    let seeds = team_seeds(season, &season_matches);
+    if seeds.is_empty() {
+        return Err(CommandError::User(String::from("No teams are seeded")));
+    }
+    let mut resp = String::from("Seeds:\n");
+    for (i, team) in seeds.iter().enumerate() {
+        let manual = season.is_some_and(|s| s.seeds.iter().any(|t| t.id == team.id));
+        resp.push_str(format!("{}. {}{}\n", i + 1, team.name, if manual { "" } else { " _(from standings)_" }).as_str());
+    }
+    Ok(resp.into())
+}
+
+async fn handle_round_robin(context: &Context, guild_id: GuildId, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(options, "division") {
+        name = division_name.trim().to_string();
+    }
+    let mut double = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(double_option)) = find_option(options, "double") {
+        double = *double_option;
+    }
+    let start_week = week_option(options, "startweek")?.unwrap_or(1);
+    let mut series_type = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(type_str)) = find_option(options, "type") {
+        series_type = SeriesType::from_str(type_str).ok();
+    }
+    let mut data = context.data.write().await;
+    let seasons: &Vec<Season> = data.guild::<Seasons>(guild_id);
+    let season = match current_season(seasons) {
+        Some(s) => s.clone(),
+        None => return Err(CommandError::User(String::from("There is no current season. Start one with `/admin newseason`"))),
+    };
+    let division = match find_division(&season, &name) {
+        Some(d) => d.clone(),
+        None => return Err(CommandError::User(format!("Division `{}` does not exist in the current season", name))),
+    };
+    if division.teams.len() < 2 {
+        return Err(CommandError::User(format!("Division `{}` needs at least two teams", division.name)));
+    }
+    let existing = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| m.season.as_ref() == Some(&season.name) && m.division.as_ref() == Some(&division.name))
+        .count();
+    let mut pending = Vec::new();
+    let mut preview = format!("Round robin preview for `{}` ({}):\n", division.name, if double { "double" } else { "single" });
+    let rounds = round_robin_rounds(&division.teams, double);
+    if start_week.checked_add(rounds.len() as u32).is_none() {
+        return Err(CommandError::User(format!("`startweek` is too high for a schedule of {} weeks", rounds.len())));
+    }
+    for (round, pairings) in rounds.into_iter().enumerate() {
+        let week = start_week + round as u32;
+        let week_str: Vec<String> = pairings.iter().map(|(home, away)| format!("{} vs {}", home.name, away.name)).collect();
+        if preview.len() < 1600 {
+            preview.push_str(format!("**Week {}:** {}\n", week, week_str.join(", ")).as_str());
+        }
+        for (home, away) in pairings {
+            pending.push(Match {
+                id: Uuid::new_v4(),
+                short_id: String::new(),
+                team_one: home,
+                team_two: away,
+                note: None,
+                date_added: Utc::now(),
+                match_state: MatchState::Entered,
+                schedule_info: None,
+                setup_info: None,
+                result: None,
+                series_type: series_type.clone(),
+                season: Some(season.name.clone()),
+                division: Some(division.name.clone()),
+                week: Some(week),
+                thread_id: None,
+                lobby: None,
+                casters: Vec::new(),
+                disputes: Vec::new(),
+            });
+        }
+    }
+    if preview.len() >= 1600 {
+        preview.push_str("...\n");
+    }
+    preview.push_str(format!("\n{} matches will be created.", pending.len()).as_str());
+    if existing > 0 {
+        preview.push_str(format!(" Note: this division already has {} matches this season.", existing).as_str());
+    }
+    preview.push_str(" Use `/admin confirmschedule` to add them.");
+    let pending_matches: &mut Vec<Match> = data.guild_mut::<PendingMatches>(guild_id);
+    *pending_matches = pending;
+    Ok(preview.into())
+}
+
+async fn handle_confirm_schedule(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let pending: Vec<Match> = data.guild_mut::<PendingMatches>(guild_id).drain(..).collect();
+    if pending.is_empty() {
+        return Err(CommandError::User(String::from("There is no schedule to confirm. Preview one with `/admin roundrobin`")));
+    }
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let count = pending.len();
+    for mut m in pending {
+        m.short_id = next_short_id(&mut last_id);
+        matches.push(m);
+    }
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(format!("Successfully added {} matches", count).into())
+}
+
+fn create_swiss_round(tournament: &mut SwissTournament, matches: &mut Vec<Match>, last_id: &mut u32) -> String {
+    let (pairings, bye) = pair_next_round(tournament, matches);
+    let round = tournament.rounds.len() + 1;
+    let mut resp = format!("Swiss `{}` round {} pairings:\n", tournament.name, round);
+    let mut round_ids = Vec::new();
+    for (team_one, team_two) in pairings {
+        let new_match = Match {
+            id: Uuid::new_v4(),
+            short_id: next_short_id(last_id),
+            team_one,
+            team_two,
+            note: Some(format!("{} - Round {}", tournament.name, round)),
+            date_added: Utc::now(),
+            match_state: MatchState::Entered,
+            schedule_info: None,
+            setup_info: None,
+            result: None,
+            series_type: Some(tournament.series_type.clone()),
+            season: tournament.season.clone(),
+            division: None,
+            week: None,
+            thread_id: None,
+            lobby: None,
+            casters: Vec::new(),
+            disputes: Vec::new(),
+        };
+        resp.push_str(print_match_info(&new_match, false).as_str());
+        round_ids.push(new_match.id);
+        matches.push(new_match);
+    }
+    if let Some(team) = bye {
+        resp.push_str(format!("<@&{}> has a bye this round\n", team.id).as_str());
+        tournament.byes.push(team);
+    }
+    tournament.rounds.push(round_ids);
+    resp
+}
+
+async fn handle_create_swiss(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(tournament_name)) = find_option(options, "name") {
+        name = tournament_name.trim().to_string();
+    }
+    let mut division_name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_option)) = find_option(options, "division") {
+        division_name = division_option.trim().to_string();
+    }
+    let mut series_type = SeriesType::Bo1;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(type_str)) = find_option(options, "type") {
+        series_type = SeriesType::from_str(type_str).unwrap_or(SeriesType::Bo1);
+    }
+    let mut data = context.data.write().await;
+    if data.guild::<SwissTournaments>(guild_id).iter().any(|t| t.name.eq_ignore_ascii_case(&name)) {
+        return Err(CommandError::User(format!("Swiss tournament `{}` already exists", name)));
+    }
+    let season = match current_season(data.guild::<Seasons>(guild_id)) {
+        Some(s) => s.clone(),
+        None => return Err(CommandError::User(String::from("There is no current season. Start one with `/admin newseason`"))),
+    };
+    let division = match find_division(&season, &division_name) {
+        Some(d) => d.clone(),
+        None => return Err(CommandError::User(format!("Division `{}` does not exist in the current season", division_name))),
+    };
+    if division.teams.len() < 2 {
+        return Err(CommandError::User(format!("Division `{}` needs at least two teams", division.name)));
+    }
+    let mut tournament = SwissTournament {
+        name,
+        season: Some(season.name),
+        teams: division.teams,
+        series_type,
+        rounds: Vec::new(),
+        byes: Vec::new(),
+    };
+    let before = audit_snapshot(&data, guild_id);
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let resp = create_swiss_round(&mut tournament, matches, &mut last_id);
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    let tournaments: &mut Vec<SwissTournament> = data.guild_mut::<SwissTournaments>(guild_id);
+    tournaments.push(tournament);
+    write_to_file(&guild_file(guild_id, "swiss.json"), serde_json::to_string_pretty(tournaments)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+async fn handle_next_swiss_round(context: &Context, msg: &ApplicationCommandInteraction, options: &[ApplicationCommandInteractionDataOption]) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(tournament_name)) = find_option(options, "name") {
+        name = tournament_name.trim().to_string();
+    }
+    let mut data = context.data.write().await;
+    let mut tournament = match data.guild::<SwissTournaments>(guild_id).iter().find(|t| t.name.eq_ignore_ascii_case(&name)) {
+        Some(t) => t.clone(),
+        None => return Err(CommandError::User(format!("Swiss tournament `{}` does not exist", name))),
+    };
+    let before = audit_snapshot(&data, guild_id);
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let unfinished: String = matches.iter()
+        .filter(|m| tournament.rounds.last().is_some_and(|round| round.contains(&m.id)) && m.result.is_none())
+        .map(|m| print_match_info(m, false))
+        .collect();
+    if !unfinished.is_empty() {
+        return Err(CommandError::User(format!("The current round can not be closed until every match has a result:\n{}", unfinished)));
+    }
+    let resp = create_swiss_round(&mut tournament, matches, &mut last_id);
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    let tournaments: &mut Vec<SwissTournament> = data.guild_mut::<SwissTournaments>(guild_id);
+    if let Some(existing) = tournaments.iter_mut().find(|t| t.name == tournament.name) {
+        *existing = tournament;
+    }
+    write_to_file(&guild_file(guild_id, "swiss.json"), serde_json::to_string_pretty(tournaments)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_swiss_standings(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut name = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(tournament_name)) = find_option(&msg.data.options, "name") {
+        name = tournament_name.trim().to_string();
+    }
+    let data = context.data.read().await;
+    let tournament = match data.guild::<SwissTournaments>(guild_id).iter().find(|t| t.name.eq_ignore_ascii_case(&name)) {
+        Some(t) => t,
+        None => return Err(CommandError::User(format!("Swiss tournament `{}` does not exist", name))),
+    };
+    let matches: &Vec<Match> = data.guild::<Matches>(guild_id);
+    let mut resp = format!("Swiss `{}` after round {}:\n", tournament.name, tournament.rounds.len());
+    for (i, record) in swiss_records(tournament, matches).iter().enumerate() {
+        resp.push_str(format!("{}. {} - `{}W {}L` Buchholz: `{}`\n", i + 1, record.team.name, record.wins, record.losses, record.buchholz).as_str());
+    }
+    Ok(resp.into())
+}
+
+fn find_bracket<'a>(brackets: &'a [Bracket], msg: &ApplicationCommandInteraction) -> Result<&'a Bracket, String> {
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(name)) = find_option(&msg.data.options, "name") {
+        return brackets.iter()
+            .find(|b| b.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or(format!("Bracket `{}` does not exist", name.trim()));
+    }
+    brackets.last().ok_or_else(|| String::from("No brackets have been created"))
+}
+
+pub(crate) async fn handle_bracket(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    let bracket = match find_bracket(data.guild::<Brackets>(guild_id), msg) {
+        Ok(bracket) => bracket,
+        Err(error) => return Err(CommandError::User(error)),
+    };
+    let mut resp = bracket_text(bracket, data.guild::<Matches>(guild_id));
+    if resp.len() > 1900 {
+        let cutoff = resp[..1850].rfind("\n\n").unwrap_or(1850);
+        resp.truncate(cutoff);
+        resp.push_str("\n...\n```_Bracket is too large to list in full, see the image below_");
+    }
+    Ok(resp.into())
+}
+
+pub(crate) async fn handle_bracket_image(context: &Context, msg: &ApplicationCommandInteraction) {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let png = {
+        let data = context.data.read().await;
+        match find_bracket(data.guild::<Brackets>(guild_id), msg) {
+            Ok(bracket) => bracket_png(bracket, data.guild::<Matches>(guild_id)),
+            Err(_) => return,
+        }
+    };
+    let png = match png {
+        Ok(png) => png,
+        Err(why) => return eprintln!("Cannot render bracket: {}", why),
+    };
+    let followup = msg.create_followup_message(&context.http, |message| {
+        message.add_file(AttachmentType::Bytes { data: Cow::from(png), filename: String::from("bracket.png") })
+    }).await;
+    if let Err(why) = followup {
+        eprintln!("Cannot send bracket image: {}", why);
+    }
+}
+
+/// Opens the match's discussion thread on the `/setup` reply, or reopens the one created by an earlier setup.
+pub(crate) async fn handle_setup_thread(context: &Context, msg: &ApplicationCommandInteraction) {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let m = {
+        let data = context.data.read().await;
+        let match_id = data.guild::<Setup>(guild_id).match_id;
+        match data.guild::<Matches>(guild_id).iter().find(|m| Some(m.id) == match_id) {
+            Some(m) => m.clone(),
+            None => return,
+        }
+    };
+    let thread_id = match m.thread_id {
+        Some(thread_id) => match thread_id.edit_thread(&context.http, |t| t.archived(false)).await {
+            Ok(_) => thread_id,
+            Err(why) => return eprintln!("Cannot reopen match thread: {}", why),
+        },
+        None => {
+            // Threads can not be nested, so a setup run inside a thread gets a starter message in the parent channel
+            let parent = thread_parent(context, msg.channel_id).await;
+            let starter = if parent == msg.channel_id {
+                msg.get_interaction_response(&context.http).await
+            } else {
+                parent.say(&context.http, format!("Map veto of match `{}` {} vs {}", m.short_id, m.team_one.name, m.team_two.name)).await
+            };
+            let starter = match starter {
+                Ok(starter) => starter,
+                Err(why) => return eprintln!("Cannot find setup response: {}", why),
+            };
+            let thread = parent.create_public_thread(&context.http, starter.id, |t| {
+                t.name(format!("{} vs {}", m.team_one.name, m.team_two.name)).auto_archive_duration(1440)
+            }).await;
+            match thread {
+                Ok(thread) => thread.id,
+                Err(why) => return eprintln!("Cannot create match thread: {}", why),
+            }
+        }
+    };
+    let intro = thread_id.send_message(&context.http, |message| {
+        message.content(format!("<@&{}> <@&{}> map veto for match `{}` has started. Use `/ban`, `/pick`, `/attack` and `/defense` to take your turns.", m.team_one.id, m.team_two.id, m.short_id))
+            .allowed_mentions(|mentions| mentions.roles(vec![m.team_one.id, m.team_two.id]))
+    }).await;
+    if let Err(why) = intro {
+        eprintln!("Cannot post to match thread: {}", why);
+    }
+    let mut data = context.data.write().await;
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    if let Some(stored) = matches.iter_mut().find(|s| s.id == m.id) {
+        stored.thread_id = Some(thread_id);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(matches) {
+        if let Err(why) = write_to_file(&guild_file(guild_id, "matches.json"), json).await {
+            eprintln!("Cannot save match thread: {}", why);
+        }
+    }
+}
+
+/// Creates the lobby channels of the match that was just set up when the guild has lobbies enabled.
+pub(crate) async fn handle_setup_lobby(context: &Context, msg: &ApplicationCommandInteraction) {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let (m, admin_role_id) = {
+        let data = context.data.read().await;
+        let config = data.get::<Config>().unwrap().guild(guild_id);
+        if config.lobby.is_none() {
+            return;
+        }
+        let match_id = data.guild::<Setup>(guild_id).match_id;
+        match data.guild::<Matches>(guild_id).iter().find(|m| Some(m.id) == match_id && m.lobby.is_none()) {
+            Some(m) => (m.clone(), config.admin_role_id),
+            None => return,
+        }
+    };
+    let lobby = match create_match_lobby(context, guild_id, &m, admin_role_id).await {
+        Ok(lobby) => lobby,
+        Err(why) => return eprintln!("Cannot create match lobby: {}", why),
+    };
+    let mut data = context.data.write().await;
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    if let Some(stored) = matches.iter_mut().find(|s| s.id == m.id) {
+        stored.lobby = Some(lobby);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(matches) {
+        if let Err(why) = write_to_file(&guild_file(guild_id, "matches.json"), json).await {
+            eprintln!("Cannot save match lobby: {}", why);
+        }
+    }
+}
+
+pub(crate) async fn handle_standings(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    let guild_id = guild_id(msg)?;
+    let mut division = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(&msg.data.options, "division") {
+        division = Some(division_name.to_lowercase());
+    }
+    let data = context.data.write().await;
+    let seasons: &Vec<Season> = data.guild::<Seasons>(guild_id);
+    let season = current_season(seasons).map(|s| s.name.clone());
+    let matches: Vec<Match> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| season.is_none() || m.season == season)
+        .filter(|m| division.is_none() || m.division.as_ref().map(|d| d.to_lowercase()) == division)
+        .cloned()
+        .collect();
+    if matches.iter().all(|m| m.result.is_none()) {
+        return Err(CommandError::User(String::from("No results have been recorded")));
+    }
+    Ok(print_standings(&matches).into())
+}
+
+pub(crate) async fn handle_edit_match(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let mut match_id = String::new();
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(id)) = find_option(&msg.data.options, "matchid") {
+        match_id = id.clone();
+    }
+    let mut team_one = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_one_role)) = find_option(&msg.data.options, "teamone") {
+        team_one = Some(RolePartial { id: team_one_role.id, name: team_one_role.name.to_string(), guild_id: team_one_role.guild_id });
+    }
+    let mut team_two = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Role(team_two_role)) = find_option(&msg.data.options, "teamtwo") {
+        team_two = Some(RolePartial { id: team_two_role.id, name: team_two_role.name.to_string(), guild_id: team_two_role.guild_id });
+    }
+    let mut swap = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(swap_option)) = find_option(&msg.data.options, "swap") {
+        swap = *swap_option;
+    }
+    let mut force = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(force_option)) = find_option(&msg.data.options, "force") {
+        force = *force_option;
+    }
+    let mut clear_result = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(clear_option)) = find_option(&msg.data.options, "clearresult") {
+        clear_result = *clear_option;
+    }
+    let mut note = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(note_str)) = find_option(&msg.data.options, "note") {
+        note = Some(note_str.clone());
+    }
+    let mut series_type = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(type_str)) = find_option(&msg.data.options, "type") {
+        series_type = SeriesType::from_str(type_str).ok();
+    }
+    let mut match_state = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(state_str)) = find_option(&msg.data.options, "state") {
+        match_state = MatchState::from_str(state_str).ok();
+    }
+    let mut division = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(division_name)) = find_option(&msg.data.options, "division") {
+        division = Some(division_name.clone());
+    }
+    let week = week_option(&msg.data.options, "week")?;
+    let mut date = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(date_str)) = find_option(&msg.data.options, "date") {
+        if let Ok(date_result) = NaiveDate::parse_from_str(date_str, "%m/%d/%Y") {
+            date = Some(date_result);
         } else {
-            return String::from("Unable to parse match ID");
+            return Err(CommandError::User(String::from("Incorrect date format. Please use correct format (Month/Day/Year) i.e. `12/23/2022`")));
         }
     }
+    let mut time = None;
+    if let Some(ApplicationCommandInteractionDataOptionValue::String(time_str)) = find_option(&msg.data.options, "time") {
+        time = Some(time_str.clone());
+    }
+    if swap && (team_one.is_some() || team_two.is_some()) {
+        return Err(CommandError::User(String::from("Use either `swap` or `teamone`/`teamtwo`, not both")));
+    }
     let mut data = context.data.write().await;
-    let matches: &mut Vec<Match> = data.get_mut::<Matches>().unwrap();
-    let match_index = matches.iter().position(|m| m.id == parsed_match_id.unwrap());
-    if let Some(index) = match_index {
-        matches.remove(index);
-    } else {
-        return String::from("Could not find match");
+    let before = audit_snapshot(&data, guild_id);
+    let setup_match_id = data.guild::<Setup>(guild_id).match_id;
+    let seasons: Vec<Season> = data.guild::<Seasons>(guild_id).clone();
+    let bracket_match_ids: Vec<Uuid> = data.guild::<Brackets>(guild_id).iter().flat_map(|b| b.nodes.iter().filter_map(|n| n.match_id)).collect();
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let find_match = matches.iter_mut().find(|m| is_match_id(m, &match_id));
+    let m = match find_match {
+        Some(m) => m,
+        None => return Err(CommandError::User(String::from("Could not find match"))),
+    };
+    let teams_changed = swap
+        || team_one.as_ref().is_some_and(|t| t.id != m.team_one.id)
+        || team_two.as_ref().is_some_and(|t| t.id != m.team_two.id);
+    if teams_changed {
+        if setup_match_id == Some(m.id) {
+            return Err(CommandError::User(String::from("This match is currently running `/setup`. Cancel it before changing teams")));
+        }
+        if m.result.is_some() {
+            return Err(CommandError::User(String::from("This match has a recorded result. Teams can not be changed")));
+        }
+        if m.setup_info.is_some() && !force {
+            return Err(CommandError::User(String::from("This match has a completed setup and its veto history refers to the current teams. Use `force:true` to change teams anyway")));
+        }
+    }
+    if series_type.is_some() && series_type != m.series_type && (m.setup_info.is_some() || setup_match_id == Some(m.id)) {
+        return Err(CommandError::User(String::from("The map veto of this match has already started. Its series type can not be changed")));
+    }
+    if clear_result && m.result.is_some() && bracket_match_ids.contains(&m.id) {
+        return Err(CommandError::User(String::from("This is a bracket match and its winner has already advanced. Use `/admin setresult` to change its result")));
+    }
+    let result = if clear_result { None } else { m.result.clone() };
+    // Clearing a result reopens the match unless its veto is done, which also marks it completed
+    let reopened = if m.schedule_info.is_some() || date.is_some() { MatchState::Scheduled } else { MatchState::Entered };
+    let new_state = match_state.clone().unwrap_or_else(|| if clear_result && m.setup_info.is_none() { reopened } else { m.match_state.clone() });
+    if result.is_some() && new_state != MatchState::Completed {
+        return Err(CommandError::User(String::from("This match has a result and stays completed. Clear the result with `clearresult:true` to change its state")));
     }
-    write_to_file("matches.json", serde_json::to_string_pretty(matches).unwrap()).await;
-    String::from("Successfully deleted match")
+    if new_state == MatchState::Completed && result.is_none() && m.setup_info.is_none() {
+        return Err(CommandError::User(String::from("This match has no result or completed setup. Record a result with `/report` or `/admin setresult` instead")));
+    }
+    let new_team_one = team_one.unwrap_or_else(|| m.team_one.clone());
+    let new_team_two = team_two.unwrap_or_else(|| m.team_two.clone());
+    if new_team_one.id == new_team_two.id {
+        return Err(CommandError::User(String::from("A team can not play against itself")));
+    }
+    if date.is_some() != time.is_some() && m.schedule_info.is_none() {
+        return Err(CommandError::User(String::from("This match is not scheduled yet. Provide both `date` and `time`")));
+    }
+    if let Some(division_name) = &division {
+        let match_season = seasons.iter().find(|s| Some(&s.name) == m.season.as_ref());
+        match match_season.and_then(|s| find_division(s, division_name)) {
+            Some(d) => division = Some(d.name.clone()),
+            None => return Err(CommandError::User(format!("Division `{}` does not exist in this match's season", division_name))),
+        }
+    }
+    m.team_one = new_team_one;
+    m.team_two = new_team_two;
+    if swap {
+        std::mem::swap(&mut m.team_one, &mut m.team_two);
+    }
+    if note.is_some() { m.note = note; }
+    if series_type.is_some() { m.series_type = series_type; }
+    if date.is_some() || time.is_some() {
+        let current = m.schedule_info.clone();
+        m.schedule_info = Some(ScheduleInfo {
+            date: date.or_else(|| current.as_ref().map(|s| s.date)).unwrap(),
+            time_str: time.or_else(|| current.map(|s| s.time_str)).unwrap(),
+        });
+    }
+    m.result = result;
+    m.match_state = new_state;
+    if division.is_some() { m.division = division; }
+    if week.is_some() { m.week = week; }
+    let mut resp = String::from("Successfully updated match\n");
+    resp.push_str(print_match_info(m, true).as_str());
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(resp.into())
 }
 
-pub(crate) async fn handle_cancel(context: &Context, msg: &ApplicationCommandInteraction) -> String {
-    let admin_check = admin_check(context, msg).await;
-    if let Err(error) = admin_check { return error; }
-    let maps = get_maps(context).await;
+pub(crate) async fn handle_restore(context: &Context, msg: &ApplicationCommandInteraction) -> CommandResult {
+    admin_check(context, msg).await?;
+    let guild_id = guild_id(msg)?;
+    let snapshot = required_string(&msg.data.options, "snapshot")?.trim();
+    let mut confirm = false;
+    if let Some(ApplicationCommandInteractionDataOptionValue::Boolean(confirm_option)) = find_option(&msg.data.options, "confirm") {
+        confirm = *confirm_option;
+    }
+    let (legacy, keep) = {
+        let data = context.data.read().await;
+        let config = data.get::<Config>().unwrap();
+        (config.discord.guild_id == Some(*guild_id.as_u64()), config.backups.keep)
+    };
+    if !guild_backups(guild_id, legacy).iter().any(|b| b == snapshot) {
+        return Err(CommandError::User(format!("Could not find snapshot `{}`", snapshot)));
+    }
+    let restored = read_backup_matches(snapshot, guild_id, legacy)?;
+    let preview = {
+        let data = context.data.read().await;
+        restore_preview(data.guild::<Matches>(guild_id), &restored)
+    };
+    if !confirm {
+        let mut resp = format!("Restoring `{}` would change {} matches:\n", snapshot, preview.len());
+        for line in &preview {
+            if resp.len() + line.len() > 1800 {
+                resp.push_str("...\n");
+                break;
+            }
+            resp.push_str(format!("{}\n", line).as_str());
+        }
+        resp.push_str("Seasons, brackets, Swiss tournaments and deleted matches are restored along with them. Shared maps and Riot IDs are left as they are.\n");
+        resp.push_str("Run `/restore` again with `confirm: True` to restore it");
+        return Ok(Response::ephemeral(resp));
+    }
+    // Backups from before seasons, brackets and deleted matches were included only restore matches
+    let seasons: Option<Vec<Season>> = read_backup_file(snapshot, guild_id, "seasons.json", legacy)?;
+    let brackets: Option<Vec<Bracket>> = read_backup_file(snapshot, guild_id, "brackets.json", legacy)?;
+    let swiss: Option<Vec<SwissTournament>> = read_backup_file(snapshot, guild_id, "swiss.json", legacy)?;
+    let deleted: Option<Vec<DeletedMatch>> = read_backup_file(snapshot, guild_id, "deleted_matches.json", false)?;
+    let backup = create_backup(Some(guild_id), legacy, "restore")?;
+    prune_backups(&backup, keep)?;
     let mut data = context.data.write().await;
-    let draft: &mut Setup = data.get_mut::<Setup>().unwrap();
-    if draft.current_phase == Idle {
-        return String::from(" command only valid during `/setup` process");
+    let before = audit_snapshot(&data, guild_id);
+    if let Some(seasons) = seasons {
+        write_to_file(&guild_file(guild_id, "seasons.json"), serde_json::to_string_pretty(&seasons)?).await?;
+        *data.guild_mut::<Seasons>(guild_id) = seasons;
+    }
+    if let Some(brackets) = brackets {
+        write_to_file(&guild_file(guild_id, "brackets.json"), serde_json::to_string_pretty(&brackets)?).await?;
+        *data.guild_mut::<Brackets>(guild_id) = brackets;
+    }
+    if let Some(swiss) = swiss {
+        write_to_file(&guild_file(guild_id, "swiss.json"), serde_json::to_string_pretty(&swiss)?).await?;
+        *data.guild_mut::<SwissTournaments>(guild_id) = swiss;
+    }
+    let deleted_matches: &mut Vec<DeletedMatch> = data.guild_mut::<DeletedMatches>(guild_id);
+    if let Some(deleted) = deleted {
+        *deleted_matches = deleted;
+    }
+    deleted_matches.retain(|d| !restored.iter().any(|m| m.id == d.deleted_match.id));
+    write_to_file(&guild_file(guild_id, "deleted_matches.json"), serde_json::to_string_pretty(deleted_matches)?).await?;
+    let last_id = restored.iter().filter_map(short_id_number).fold(*data.guild::<MatchCounter>(guild_id), u32::max);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    *matches = restored;
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(matches)?).await?;
+    save_match_counter(&mut data, guild_id, last_id).await?;
+    queue_audit(&mut data, guild_id, msg, before);
+    Ok(format!("Restored `{}`, {} matches changed. The previous data was backed up as `{}`", snapshot, preview.len(), backup).into())
+}
+
+pub(crate) async fn handle_snapshot_autocomplete(context: &Context, autocomplete: &AutocompleteInteraction) {
+    let query = autocomplete.data.options.iter()
+        .find(|o| o.focused)
+        .and_then(|o| o.value.as_ref().and_then(|v| v.as_str()))
+        .unwrap_or_default()
+        .to_lowercase();
+    let backups = match autocomplete.guild_id {
+        Some(guild_id) => {
+            let data = context.data.read().await;
+            guild_backups(guild_id, data.get::<Config>().unwrap().discord.guild_id == Some(*guild_id.as_u64()))
+        }
+        None => Vec::new(),
+    };
+    let backups: Vec<String> = backups.into_iter()
+        .filter(|b| b.to_lowercase().contains(&query))
+        .take(25)
+        .collect();
+    let result = autocomplete.create_autocomplete_response(&context.http, |response| {
+        for backup in &backups {
+            response.add_string_choice(backup, backup);
+        }
+        response
+    }).await;
+    if let Err(why) = result {
+        eprintln!("Cannot respond to autocomplete: {}", why);
+    }
+}
+
+/// Suggests the maps left in the running veto, or the guild's map pool when no veto is running.
+pub(crate) async fn handle_map_autocomplete(context: &Context, autocomplete: &AutocompleteInteraction) {
+    let query = autocomplete.data.options.iter()
+        .find(|o| o.focused)
+        .and_then(|o| o.value.as_ref().and_then(|v| v.as_str()))
+        .unwrap_or_default()
+        .to_lowercase();
+    let guild_id = match autocomplete.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let maps: Vec<String> = {
+        let data = context.data.read().await;
+        let setup: &Setup = data.guild::<Setup>(guild_id);
+        let maps = if setup.current_phase == MapVeto { setup.maps_remaining.clone() } else { guild_maps(&data, guild_id) };
+        maps.into_iter().filter(|m| m.to_lowercase().contains(&query)).take(25).collect()
+    };
+    let result = autocomplete.create_autocomplete_response(&context.http, |response| {
+        for map in &maps {
+            response.add_string_choice(map.to_uppercase(), map);
+        }
+        response
+    }).await;
+    if let Err(why) = result {
+        eprintln!("Cannot respond to autocomplete: {}", why);
     }
-    reset_setup(draft, maps);
-    String::from("`/setup` process cancelled.")
 }
 
+pub(crate) async fn handle_match_id_autocomplete(context: &Context, autocomplete: &AutocompleteInteraction) {
+    let mut query = String::new();
+    let mut options = &autocomplete.data.options;
+    while let Some(subcommand) = options.iter().find(|o| !o.options.is_empty()) {
+        options = &subcommand.options;
+    }
+    if let Some(focused) = options.iter().find(|o| o.focused) {
+        if let Some(value) = focused.value.as_ref().and_then(|v| v.as_str()) {
+            query = value.to_lowercase();
+        }
+    }
+    let guild_id = match autocomplete.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let data = context.data.read().await;
+    let matches: Vec<&Match> = match autocomplete.data.name.as_str() {
+        "restorematch" | "purgematch" => data.guild::<DeletedMatches>(guild_id).iter().map(|d| &d.deleted_match).collect(),
+        _ => data.guild::<Matches>(guild_id).iter().collect(),
+    };
+    let choices: Vec<&Match> = matches.into_iter()
+        .filter(|m| query.is_empty()
+            || m.short_id.to_lowercase().starts_with(&query)
+            || m.team_one.name.to_lowercase().contains(&query)
+            || m.team_two.name.to_lowercase().contains(&query))
+        .take(25)
+        .collect();
+    let result = autocomplete.create_autocomplete_response(&context.http, |response| {
+        for m in choices {
+            response.add_string_choice(format!("{} - {} vs {}", m.short_id, m.team_one.name, m.team_two.name), &m.short_id);
+        }
+        response
+    }).await;
+    if let Err(why) = result {
+        eprintln!("Cannot respond to autocomplete: {}", why);
+    }
+}
@@ -1,34 +1,131 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
 use std::str::FromStr;
 use chrono::{DateTime, NaiveDate, Utc};
 
 
 use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
 use serenity::async_trait;
+use serenity::builder::{CreateComponents, CreateEmbed};
 use serenity::Client;
 use serenity::client::Context;
 use serenity::framework::standard::StandardFramework;
-use serenity::model::guild::Role;
-use serenity::model::prelude::{GuildId, Interaction, InteractionResponseType, Ready, RoleId};
-use serenity::model::prelude::application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType};
-use serenity::prelude::{EventHandler, TypeMapKey};
+use serenity::model::guild::{Guild, Role};
+use serenity::model::prelude::{ChannelId, GuildId, Interaction, InteractionResponseType, Ready, RoleId, UserId};
+use serenity::model::prelude::application_command::{ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandOptionType};
+use serenity::model::prelude::InteractionApplicationCommandCallbackDataFlags;
+use serenity::prelude::{EventHandler, TypeMap, TypeMapKey};
 use uuid::Uuid;
 use crate::SeriesType::{Bo1, Bo3, Bo5};
 
+mod audit;
+mod bracket;
 mod commands;
+mod render;
+mod round_robin;
+mod stats;
+mod swiss;
 mod utils;
+mod veto;
 
 #[derive(Serialize, Deserialize)]
 struct Config {
     discord: DiscordConfig,
+    #[serde(default)]
+    seeding: SeedingConfig,
+    #[serde(default)]
+    guilds: HashMap<u64, GuildConfig>,
+    #[serde(default)]
+    backups: BackupConfig,
+}
+
+/// Rotating backups of the match data, taken on a schedule and before destructive admin commands.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct BackupConfig {
+    /// Hours between scheduled backups, no scheduled backups when 0
+    interval_hours: u64,
+    /// Number of backups of each kind kept, older ones are removed. Scheduled backups and the
+    /// backups taken before each command in each server rotate separately
+    keep: usize,
+}
+
+/// Settings of a single guild. Guilds without an entry use the defaults.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GuildConfig {
+    admin_role_id: Option<u64>,
+    /// Channels commands may be used in, any channel when empty
+    channels: Vec<u64>,
+    /// Also allow commands in threads of those channels, such as match threads
+    allow_threads: bool,
+    maps: Vec<String>,
+    veto: VetoTemplates,
+    /// Lobby channels per match, no lobbies when unset
+    lobby: Option<LobbyConfig>,
+    cast: CastConfig,
+    /// Channel every audited change is posted to
+    audit_channel_id: Option<u64>,
+    /// Defaults to the top level `seeding` settings
+    seeding: Option<SeedingConfig>,
+}
+
+/// Category with a text channel and a voice channel per team, created when a match setup starts.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct LobbyConfig {
+    /// Minutes after the result is recorded before the lobby is deleted
+    cleanup_minutes: u64,
+}
+
+/// Caster sign-ups for scheduled matches.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct CastConfig {
+    /// Role allowed to claim matches, casting is disabled when unset
+    caster_role_id: Option<u64>,
+    max_casters: usize,
+    /// Channel the weekly broadcast schedule is posted to every Monday
+    schedule_channel_id: Option<u64>,
+}
+
+/// Ban and pick orders per series type. An empty template uses the default order.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct VetoTemplates {
+    bo1: Vec<TemplateStep>,
+    bo3: Vec<TemplateStep>,
+    bo5: Vec<TemplateStep>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TemplateStep {
+    BanOne,
+    BanTwo,
+    PickOne,
+    PickTwo,
+}
+
+/// Advantages given to the higher seeded team of a match. Every advantage is off unless enabled.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SeedingConfig {
+    veto_first: bool,
+    decider_side: bool,
+    home_slot: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 struct DiscordConfig {
     token: String,
     admin_role_id: Option<u64>,
+    channel_id: Option<u64>,
     application_id: u64,
-    guild_id: u64,
+    /// Guild the match data files of a single guild install belong to
+    guild_id: Option<u64>,
 }
 
 #[derive(PartialEq)]
@@ -77,9 +174,242 @@ struct SetupInfo {
     vetos: Vec<SetupStep>,
 }
 
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum ResultType {
+    Played,
+    Forfeit,
+    NoShow,
+    AdminOverride,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct MapScore {
+    map: String,
+    team_one_score: u32,
+    team_two_score: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct MatchResult {
+    winner: RolePartial,
+    result_type: ResultType,
+    #[serde(default)]
+    map_scores: Vec<MapScore>,
+    reason: Option<String>,
+    set_by: UserId,
+    date_set: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Division {
+    name: String,
+    teams: Vec<RolePartial>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Season {
+    name: String,
+    date_started: DateTime<Utc>,
+    date_archived: Option<DateTime<Utc>>,
+    divisions: Vec<Division>,
+    #[serde(default)]
+    seeds: Vec<RolePartial>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum BracketType {
+    SingleElimination,
+    DoubleElimination,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum BracketSide {
+    Upper,
+    Lower,
+    GrandFinal,
+    ThirdPlace,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum BracketSlot {
+    Pending,
+    Bye,
+    Team(RolePartial),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BracketNode {
+    id: usize,
+    side: BracketSide,
+    round: u32,
+    slot_one: BracketSlot,
+    slot_two: BracketSlot,
+    series_type: SeriesType,
+    winner_to: Option<(usize, u8)>,
+    loser_to: Option<(usize, u8)>,
+    winner: Option<RolePartial>,
+    completed: bool,
+    match_id: Option<Uuid>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Bracket {
+    name: String,
+    season: Option<String>,
+    bracket_type: BracketType,
+    #[serde(default)]
+    seeds: Vec<RolePartial>,
+    nodes: Vec<BracketNode>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SwissTournament {
+    name: String,
+    season: Option<String>,
+    teams: Vec<RolePartial>,
+    series_type: SeriesType,
+    rounds: Vec<Vec<Uuid>>,
+    byes: Vec<RolePartial>,
+}
+
+#[derive(Clone)]
+struct SwissRecord {
+    team: RolePartial,
+    wins: u32,
+    losses: u32,
+    buchholz: u32,
+}
+
+#[derive(Clone)]
+struct Standing {
+    team: RolePartial,
+    wins: u32,
+    losses: u32,
+    forfeit_losses: u32,
+}
+
+#[derive(Clone)]
+struct MatchFilter {
+    show_ids: bool,
+    states: Vec<MatchState>,
+    /// `Some(None)` lists only matches that were added before seasons existed.
+    season: Option<Option<String>>,
+    division: Option<String>,
+    week: Option<u32>,
+    team: Option<RoleId>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    sort_by_added: bool,
+}
+
+/// A reply to an interaction. Ephemeral replies are only shown to the user who ran the command.
+struct Response {
+    content: String,
+    embeds: Vec<CreateEmbed>,
+    components: Option<CreateComponents>,
+    ephemeral: bool,
+}
+
+impl Response {
+    fn ephemeral(content: String) -> Response {
+        Response { content, embeds: Vec::new(), components: None, ephemeral: true }
+    }
+}
+
+/// Why a command could not be completed. `User` errors are caused by the invoking user and shown
+/// to them as is, everything else is logged and replaced with a generic message.
+#[derive(Debug)]
+enum CommandError {
+    User(String),
+    MissingOption(String),
+    NotInGuild,
+    UnknownCommand(String),
+    Discord(serenity::Error),
+    Storage(String),
+}
+
+type CommandResult = Result<Response, CommandError>;
+
+impl CommandError {
+    /// The underlying cause of errors that should be logged rather than blamed on the user.
+    fn internal_cause(&self) -> Option<String> {
+        match self {
+            CommandError::UnknownCommand(name) => Some(format!("unknown command `{}`", name)),
+            CommandError::Discord(error) => Some(error.to_string()),
+            CommandError::Storage(error) => Some(error.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::User(message) => f.write_str(message),
+            CommandError::MissingOption(name) => write!(f, "Missing the `{}` option", name),
+            CommandError::NotInGuild => f.write_str("This command can only be used in a server"),
+            CommandError::UnknownCommand(_) => f.write_str("This command is no longer supported"),
+            CommandError::Discord(_) => f.write_str("There was an issue talking to Discord, please try again"),
+            CommandError::Storage(_) => f.write_str("There was an issue saving match data, please contact an admin"),
+        }
+    }
+}
+
+impl From<serenity::Error> for CommandError {
+    fn from(error: serenity::Error) -> Self {
+        CommandError::Discord(error)
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(error: serde_json::Error) -> Self {
+        CommandError::Storage(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        CommandError::Storage(error.to_string())
+    }
+}
+
+impl From<veto::VetoError> for CommandError {
+    fn from(error: veto::VetoError) -> Self {
+        CommandError::User(error.to_string())
+    }
+}
+
+impl From<String> for Response {
+    fn from(content: String) -> Self {
+        Response { content, embeds: Vec::new(), components: None, ephemeral: false }
+    }
+}
+
+#[derive(Clone, Default)]
+struct MapStat {
+    map: String,
+    picks: u32,
+    bans: u32,
+    played: u32,
+    wins: u32,
+    attack_starts: u32,
+    attack_wins: u32,
+    defense_starts: u32,
+    defense_wins: u32,
+}
+
+#[derive(Clone)]
+struct TeamStats {
+    setups: u32,
+    maps: Vec<MapStat>,
+    first_bans: Vec<(String, u32)>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct Match {
     id: Uuid,
+    #[serde(default)]
+    short_id: String,
     team_one: RolePartial,
     team_two: RolePartial,
     note: Option<String>,
@@ -87,9 +417,70 @@ struct Match {
     match_state: MatchState,
     schedule_info: Option<ScheduleInfo>,
     setup_info: Option<SetupInfo>,
+    result: Option<MatchResult>,
+    series_type: Option<SeriesType>,
+    season: Option<String>,
+    division: Option<String>,
+    week: Option<u32>,
+    /// Discussion thread created when the match was set up
+    #[serde(default)]
+    thread_id: Option<ChannelId>,
+    /// Lobby channels created for match day, removed after the result
+    #[serde(default)]
+    lobby: Option<MatchLobby>,
+    #[serde(default)]
+    casters: Vec<Caster>,
+    #[serde(default)]
+    disputes: Vec<Dispute>,
+}
+
+/// A disagreement about a match raised by one of its teams. Results cannot be reported while a
+/// dispute is open.
+#[derive(Clone, Serialize, Deserialize)]
+struct Dispute {
+    opened_by: UserId,
+    team: RolePartial,
+    reason: String,
+    date_opened: DateTime<Utc>,
+    thread_id: Option<ChannelId>,
+    resolution: Option<DisputeResolution>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+struct DisputeResolution {
+    outcome: DisputeOutcome,
+    decision: String,
+    resolved_by: UserId,
+    date_resolved: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+enum DisputeOutcome {
+    Upheld,
+    Rejected,
+}
+
+/// Tombstone of a deleted match, kept in `deleted_matches.json` until it is restored or purged.
+#[derive(Clone, Serialize, Deserialize)]
+struct DeletedMatch {
+    deleted_match: Match,
+    deleted_by: UserId,
+    date_deleted: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Caster {
+    user_id: UserId,
+    stream: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct MatchLobby {
+    category_id: ChannelId,
+    channel_ids: Vec<ChannelId>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 enum SeriesType {
     Bo1,
     Bo3,
@@ -109,6 +500,44 @@ struct SetupStep {
     map: Option<String>,
 }
 
+/// Guild state a command may change, captured before and after it runs.
+struct AuditSnapshot {
+    matches: Vec<Match>,
+    deleted: Vec<DeletedMatch>,
+    setup: Setup,
+    seasons: Vec<Season>,
+    brackets: Vec<Bracket>,
+    swiss: Vec<SwissTournament>,
+}
+
+/// A change to a match, the setup, a season, a bracket or a Swiss tournament, appended to the
+/// guild's `audit.jsonl`. `before` and `after` hold only the changed fields, or the whole item
+/// when it was added or deleted.
+#[derive(Serialize, Deserialize)]
+struct AuditEntry {
+    date: DateTime<Utc>,
+    user_id: UserId,
+    command: String,
+    target: AuditTarget,
+    match_id: Option<Uuid>,
+    short_id: Option<String>,
+    /// Name of the season, bracket or Swiss tournament
+    name: Option<String>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum AuditTarget {
+    Match,
+    Setup,
+    /// A deleted match that was purged
+    DeletedMatch,
+    Season,
+    Bracket,
+    Swiss,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct Setup {
     team_one: Option<RolePartial>,
@@ -121,6 +550,7 @@ struct Setup {
     veto_pick_order: Vec<SetupStep>,
     current_step: usize,
     current_phase: State,
+    decider_side_pick: Option<RolePartial>,
 }
 
 #[derive(PartialEq, Serialize, Deserialize, Clone)]
@@ -141,6 +571,65 @@ struct Maps;
 
 struct Matches;
 
+struct DeletedMatches;
+
+struct Seasons;
+
+struct PendingMatches;
+
+struct Brackets;
+
+struct SwissTournaments;
+
+/// The number of the last short match ID issued in a guild.
+struct MatchCounter;
+
+struct MatchPages;
+
+/// Audit entries of a command, keyed by interaction ID until they are written after the reply.
+struct PendingAudit;
+
+/// State kept separately for every guild, stored in the `TypeMap` under `GuildState<K>`.
+trait GuildKey: Send + Sync + 'static {
+    type Value: Send + Sync;
+}
+
+struct GuildState<K>(PhantomData<K>);
+
+trait GuildData {
+    fn guild<K: GuildKey>(&self, guild_id: GuildId) -> &K::Value;
+    fn guild_mut<K: GuildKey>(&mut self, guild_id: GuildId) -> &mut K::Value;
+}
+
+impl GuildData for TypeMap {
+    fn guild<K: GuildKey>(&self, guild_id: GuildId) -> &K::Value {
+        self.get::<GuildState<K>>().and_then(|state| state.get(&guild_id)).expect("Guild state is loaded before handling commands")
+    }
+
+    fn guild_mut<K: GuildKey>(&mut self, guild_id: GuildId) -> &mut K::Value {
+        self.get_mut::<GuildState<K>>().and_then(|state| state.get_mut(&guild_id)).expect("Guild state is loaded before handling commands")
+    }
+}
+
+impl<K: GuildKey> TypeMapKey for GuildState<K> {
+    type Value = HashMap<GuildId, K::Value>;
+}
+
+impl Config {
+    fn guild(&self, guild_id: GuildId) -> GuildConfig {
+        let mut config = self.guilds.get(guild_id.as_u64()).cloned().unwrap_or_default();
+        if config.admin_role_id.is_none() {
+            config.admin_role_id = self.discord.admin_role_id;
+        }
+        if config.seeding.is_none() {
+            config.seeding = Some(self.seeding.clone());
+        }
+        if config.channels.is_empty() && self.discord.guild_id == Some(*guild_id.as_u64()) {
+            config.channels.extend(self.discord.channel_id);
+        }
+        config
+    }
+}
 
 impl TypeMapKey for Config {
     type Value = Config;
@@ -158,19 +647,52 @@ impl TypeMapKey for Maps {
     type Value = Vec<String>;
 }
 
-impl TypeMapKey for Setup {
+impl GuildKey for Setup {
     type Value = Setup;
 }
 
-impl TypeMapKey for Matches {
+impl GuildKey for Matches {
     type Value = Vec<Match>;
 }
 
+impl GuildKey for DeletedMatches {
+    type Value = Vec<DeletedMatch>;
+}
+
+impl GuildKey for Seasons {
+    type Value = Vec<Season>;
+}
+
+impl GuildKey for PendingMatches {
+    type Value = Vec<Match>;
+}
+
+impl GuildKey for Brackets {
+    type Value = Vec<Bracket>;
+}
+
+impl GuildKey for SwissTournaments {
+    type Value = Vec<SwissTournament>;
+}
+
+impl GuildKey for MatchCounter {
+    type Value = u32;
+}
+
+impl TypeMapKey for MatchPages {
+    type Value = HashMap<u64, MatchFilter>;
+}
+
+impl TypeMapKey for PendingAudit {
+    type Value = HashMap<u64, Vec<AuditEntry>>;
+}
+
 enum Command {
     Setup,
     Schedule,
     Addmatch,
     Deletematch,
+    Editmatch,
     Match,
     Matches,
     Maps,
@@ -180,6 +702,56 @@ enum Command {
     Pick,
     Ban,
     Help,
+    Forfeit,
+    Report,
+    Admin,
+    Standings,
+    Swiss,
+    Bracket,
+    Seeds,
+    Mapstats,
+    Teamstats,
+    H2h,
+    Cast,
+    Broadcasts,
+    Dispute,
+    Audit,
+    Restorematch,
+    Purgematch,
+    Restore,
+}
+
+impl Command {
+    /// Name of the backup taken before an admin command that can overwrite or remove match data,
+    /// `None` for commands that leave it alone. Of the admin subcommands only those that change
+    /// data are backed up.
+    fn backup_reason(&self, inc_command: &ApplicationCommandInteraction) -> Option<String> {
+        match self {
+            Command::Deletematch | Command::Purgematch | Command::Editmatch | Command::Forfeit => Some(inc_command.data.name.clone()),
+            Command::Admin => inc_command.data.options.first()
+                .filter(|subcommand| subcommand.name != "roundrobin")
+                .map(|subcommand| format!("admin-{}", subcommand.name)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig { interval_hours: 24, keep: 14 }
+    }
+}
+
+impl Default for CastConfig {
+    fn default() -> Self {
+        CastConfig { caster_role_id: None, max_casters: 2, schedule_channel_id: None }
+    }
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        LobbyConfig { cleanup_minutes: 30 }
+    }
 }
 
 impl FromStr for SeriesType {
@@ -194,15 +766,47 @@ impl FromStr for SeriesType {
     }
 }
 
-impl ToString for StepType {
-    fn to_string(&self) -> String {
-        String::from(match &self {
+impl FromStr for MatchState {
+    type Err = ();
+    fn from_str(input: &str) -> Result<MatchState, Self::Err> {
+        match input {
+            "entered" => Ok(MatchState::Entered),
+            "scheduled" => Ok(MatchState::Scheduled),
+            "completed" => Ok(MatchState::Completed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for StepType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match &self {
             StepType::Veto => "/ban",
             StepType::Pick => "/pick",
         })
     }
 }
 
+impl fmt::Display for ResultType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match &self {
+            ResultType::Played => "PLAYED",
+            ResultType::Forfeit => "FORFEIT",
+            ResultType::NoShow => "NO-SHOW",
+            ResultType::AdminOverride => "ADMIN DECISION",
+        })
+    }
+}
+
+impl fmt::Display for DisputeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match &self {
+            DisputeOutcome::Upheld => "UPHELD",
+            DisputeOutcome::Rejected => "REJECTED",
+        })
+    }
+}
+
 impl FromStr for Command {
     type Err = ();
     fn from_str(input: &str) -> Result<Command, Self::Err> {
@@ -211,6 +815,7 @@ impl FromStr for Command {
             "schedule" => Ok(Command::Schedule),
             "addmatch" => Ok(Command::Addmatch),
             "deletematch" => Ok(Command::Deletematch),
+            "editmatch" => Ok(Command::Editmatch),
             "match" => Ok(Command::Match),
             "matches" => Ok(Command::Matches),
             "maps" => Ok(Command::Maps),
@@ -220,6 +825,23 @@ impl FromStr for Command {
             "pick" => Ok(Command::Pick),
             "ban" => Ok(Command::Ban),
             "help" => Ok(Command::Help),
+            "forfeit" => Ok(Command::Forfeit),
+            "report" => Ok(Command::Report),
+            "admin" => Ok(Command::Admin),
+            "standings" => Ok(Command::Standings),
+            "swiss" => Ok(Command::Swiss),
+            "bracket" => Ok(Command::Bracket),
+            "seeds" => Ok(Command::Seeds),
+            "mapstats" => Ok(Command::Mapstats),
+            "teamstats" => Ok(Command::Teamstats),
+            "h2h" => Ok(Command::H2h),
+            "cast" => Ok(Command::Cast),
+            "broadcasts" => Ok(Command::Broadcasts),
+            "dispute" => Ok(Command::Dispute),
+            "audit" => Ok(Command::Audit),
+            "restorematch" => Ok(Command::Restorematch),
+            "purgematch" => Ok(Command::Purgematch),
+            "restore" => Ok(Command::Restore),
             _ => Err(()),
         }
     }
@@ -227,176 +849,915 @@ impl FromStr for Command {
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, context: Context, ready: Ready) {
-        let config = read_config().await.unwrap();
-        let guild_id = GuildId(config.discord.guild_id);
-        let commands = GuildId::set_application_commands(&guild_id, &context.http, |commands| {
-            return commands
-                .create_application_command(|command| {
-                    command.name("maps").description("Lists the current map pool")
-                })
-                .create_application_command(|command| {
-                    command.name("cancel").description("Cancels setup (requires admin)")
-                })
-                .create_application_command(|command| {
-                    command.name("attack").description("Select attack starting side")
-                })
-                .create_application_command(|command| {
-                    command.name("defense").description("Select defense starting side")
+    async fn ready(&self, _context: Context, ready: Ready) {
+        println!("{} is connected!", ready.user.name);
+    }
+    async fn guild_create(&self, context: Context, guild: Guild, _is_new: bool) {
+        if let Err(why) = load_guild(&context, guild.id).await {
+            eprintln!("Cannot load data of guild {}: {}", guild.name, why.internal_cause().unwrap_or_default());
+        }
+        match register_commands(&context, guild.id).await {
+            Ok(commands) => println!("Added {} slash commands to guild {}", commands.len(), guild.name),
+            Err(why) => eprintln!("Cannot add slash commands to guild {}: {}", guild.name, why),
+        }
+    }
+    async fn interaction_create(&self, context: Context, interaction: Interaction) {
+        let guild_id = match &interaction {
+            Interaction::ApplicationCommand(command) => command.guild_id,
+            Interaction::MessageComponent(component) => component.guild_id,
+            Interaction::Autocomplete(autocomplete) => autocomplete.guild_id,
+            _ => None,
+        };
+        if let Some(guild_id) = guild_id {
+            if let Err(why) = load_guild(&context, guild_id).await {
+                eprintln!("Cannot load data of guild {}: {}", guild_id, why.internal_cause().unwrap_or_default());
+                if let Interaction::ApplicationCommand(inc_command) = &interaction {
+                    if let Err(why) = create_int_resp(&context, inc_command, Response::ephemeral(why.to_string())).await {
+                        eprintln!("Cannot respond to slash command: {}", why);
+                    }
+                }
+                return;
+            }
+        }
+        if let Interaction::Autocomplete(autocomplete) = &interaction {
+            match autocomplete.data.name.as_str() {
+                "restore" => commands::handle_snapshot_autocomplete(&context, autocomplete).await,
+                "pick" | "ban" => commands::handle_map_autocomplete(&context, autocomplete).await,
+                _ => commands::handle_match_id_autocomplete(&context, autocomplete).await,
+            }
+            return;
+        }
+        if let Interaction::MessageComponent(component) = &interaction {
+            match component.data.custom_id.split(':').next() {
+                Some("matches") => commands::handle_matches_page(&context, component).await,
+                _ => eprintln!("Unknown component `{}`", component.data.custom_id),
+            }
+            return;
+        }
+        if let Interaction::ApplicationCommand(inc_command) = interaction {
+            let name = inc_command.data.name.to_lowercase();
+            let command = Command::from_str(&name);
+            let result = match &command {
+                Ok(command) => run_command(&context, &inc_command, command).await,
+                Err(_) => Err(CommandError::UnknownCommand(name)),
+            };
+            let succeeded = result.is_ok();
+            let response = result.unwrap_or_else(|err| {
+                if let Some(cause) = err.internal_cause() {
+                    eprintln!("Command `{}` failed: {}", inc_command.data.name, cause);
+                }
+                Response::ephemeral(err.to_string())
+            });
+            if let Err(why) = create_int_resp(&context, &inc_command, response).await {
+                eprintln!("Cannot respond to slash command: {}", why);
+            }
+            if let Some(guild_id) = guild_id {
+                utils::record_audit(&context, guild_id, &inc_command).await;
+            }
+            match (succeeded, command) {
+                (true, Ok(Command::Bracket)) => commands::handle_bracket_image(&context, &inc_command).await,
+                (true, Ok(Command::Setup)) => {
+                    commands::handle_setup_thread(&context, &inc_command).await;
+                    commands::handle_setup_lobby(&context, &inc_command).await;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Registers the slash commands of a guild, called whenever the bot joins or reconnects to one.
+async fn register_commands(context: &Context, guild_id: GuildId) -> serenity::Result<Vec<ApplicationCommand>> {
+    guild_id.set_application_commands(&context.http, |commands| {
+        commands
+            .create_application_command(|command| {
+                command.name("maps").description("Lists the current map pool")
+            })
+            .create_application_command(|command| {
+                command.name("cancel").description("Cancels setup (requires admin)")
+            })
+            .create_application_command(|command| {
+                command.name("attack").description("Select attack starting side")
+            })
+            .create_application_command(|command| {
+                command.name("defense").description("Select defense starting side")
+            })
+            .create_application_command(|command| {
+                command.name("help").description("DM yourself help info")
+            })
+            .create_application_command(|command| {
+                command.name("standings").description("Show team standings").create_option(|option| {
+                    option
+                        .name("division")
+                        .description("Division")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
                 })
-                .create_application_command(|command| {
-                    command.name("help").description("DM yourself help info")
+            })
+            .create_application_command(|command| {
+                command.name("match").description("Show matches").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
                 })
-                .create_application_command(|command| {
-                    command.name("match").description("Show matches").create_option(|option| {
-                        option
-                            .name("matchid")
-                            .description("Match ID")
-                            .kind(ApplicationCommandOptionType::String)
-                            .required(true)
-                    })
+            })
+            .create_application_command(|command| {
+                command.name("matches").description("Show matches").create_option(|option| {
+                    option
+                        .name("displayid")
+                        .description("Display match IDs")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
                 })
-                .create_application_command(|command| {
-                    command.name("matches").description("Show matches").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("displayid")
-                            .description("Display match IDs")
+                            .name("showcompleted")
+                            .description("Shows only completed matches")
                             .kind(ApplicationCommandOptionType::Boolean)
                             .required(false)
                     })
-                        .create_option(|option| {
-                            option
-                                .name("showcompleted")
-                                .description("Shows only completed matches")
-                                .kind(ApplicationCommandOptionType::Boolean)
-                                .required(false)
-                        })
-                })
-                .create_application_command(|command| {
-                    command.name("deletematch").description("Delete match (admin required)").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("matchid")
-                            .description("Match ID")
+                            .name("season")
+                            .description("Season (defaults to the current season, `none` for matches without a season)")
                             .kind(ApplicationCommandOptionType::String)
-                            .required(true)
+                            .required(false)
                     })
-                })
-                .create_application_command(|command| {
-                    command.name("setup").description("Setup your next match").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("type")
-                            .description("Series Type")
+                            .name("division")
+                            .description("Division")
                             .kind(ApplicationCommandOptionType::String)
-                            .required(true)
-                            .add_string_choice("Best of 1", "bo1")
-                            .add_string_choice("Best of 3", "bo3")
-                            .add_string_choice("Best of 5", "bo5")
+                            .required(false)
                     })
-                })
-                .create_application_command(|command| {
-                    command.name("pick").description("Pick a map during the map veto").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("map")
-                            .description("Map name")
-                            .kind(ApplicationCommandOptionType::String)
-                            .required(true)
-                            .add_string_choice("Ascent", "ascent")
-                            .add_string_choice("Bind", "bind")
-                            .add_string_choice("Breeze", "breeze")
-                            .add_string_choice("Fracture", "fracture")
-                            .add_string_choice("Haven", "haven")
-                            .add_string_choice("Icebox", "icebox")
-                            .add_string_choice("Split", "split")
+                            .name("week")
+                            .description("Week")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(false)
                     })
-                })
-                .create_application_command(|command| {
-                    command.name("ban").description("Ban a map during the map veto").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("map")
-                            .description("Map name")
-                            .kind(ApplicationCommandOptionType::String)
-                            .required(true)
-                            .add_string_choice("Ascent", "ascent")
-                            .add_string_choice("Bind", "bind")
-                            .add_string_choice("Breeze", "breeze")
-                            .add_string_choice("Fracture", "fracture")
-                            .add_string_choice("Haven", "haven")
-                            .add_string_choice("Icebox", "icebox")
-                            .add_string_choice("Split", "split")
+                            .name("team")
+                            .description("Team")
+                            .kind(ApplicationCommandOptionType::Role)
+                            .required(false)
                     })
-                })
-                .create_application_command(|command| {
-                    command.name("addmatch").description("Add match to schedule (admin required)").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("teamone")
-                            .description("Team 1 (Home)")
-                            .kind(ApplicationCommandOptionType::Role)
-                            .required(true)
-                    }).create_option(|option| {
+                            .name("myteam")
+                            .description("Only show your team's matches")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                            .required(false)
+                    })
+                    .create_option(|option| {
                         option
-                            .name("teamtwo")
-                            .description("Team 2 (Away)")
-                            .kind(ApplicationCommandOptionType::Role)
-                            .required(true)
-                    }).create_option(|option| {
+                            .name("state")
+                            .description("Match state (overrides showcompleted)")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                            .add_string_choice("Entered", "entered")
+                            .add_string_choice("Scheduled", "scheduled")
+                            .add_string_choice("Completed", "completed")
+                            .add_string_choice("All", "all")
+                    })
+                    .create_option(|option| {
                         option
-                            .name("note")
-                            .description("Note")
+                            .name("from")
+                            .description("Scheduled on or after date (MM/DD/YYYY)")
                             .kind(ApplicationCommandOptionType::String)
                             .required(false)
                     })
-                })
-                .create_application_command(|command| {
-                    command.name("schedule").description("Schedule your next match").create_option(|option| {
+                    .create_option(|option| {
                         option
-                            .name("date")
-                            .description("Date (Month/Day/Year)")
+                            .name("to")
+                            .description("Scheduled on or before date (MM/DD/YYYY)")
                             .kind(ApplicationCommandOptionType::String)
-                            .required(true)
-                    }).create_option(|option| {
+                            .required(false)
+                    })
+                    .create_option(|option| {
                         option
-                            .name("time")
-                            .description("Time (include timezone) i.e. 10EST")
+                            .name("sort")
+                            .description("Sort order (defaults to scheduled date)")
                             .kind(ApplicationCommandOptionType::String)
-                            .required(true)
+                            .required(false)
+                            .add_string_choice("Scheduled date", "scheduled")
+                            .add_string_choice("Date added", "added")
                     })
+            })
+            .create_application_command(|command| {
+                command.name("deletematch").description("Delete match (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
                 })
-            ;
-        }).await;
-        println!("{} is connected!", ready.user.name);
-        println!("Added these guild slash commands: {:#?}", commands);
-    }
-    async fn interaction_create(&self, context: Context, interaction: Interaction) {
-        if let Interaction::ApplicationCommand(inc_command) = interaction {
-            let command = Command::from_str(&inc_command.data.name.as_str().to_lowercase()).expect("Expected valid command");
-            let content: String = match command {
-                Command::Setup => commands::handle_setup(&context, &inc_command).await,
-                Command::Addmatch => commands::handle_add_match(&context, &inc_command).await,
-                Command::Deletematch => commands::handle_delete_match(&context, &inc_command).await,
-                Command::Schedule => commands::handle_schedule(&context, &inc_command).await,
-                Command::Match => commands::handle_match(&context, &inc_command).await,
-                Command::Matches => commands::handle_matches(&context, &inc_command).await,
-                Command::Maps => commands::handle_map_list(&context).await,
-                Command::Defense => commands::handle_defense_option(&context, &inc_command).await,
-                Command::Attack => commands::handle_attack_option(&context, &inc_command).await,
-                Command::Pick => commands::handle_pick_option(&context, &inc_command).await,
-                Command::Ban => commands::handle_ban_option(&context, &inc_command).await,
-                Command::Cancel => commands::handle_cancel(&context, &inc_command).await,
-                Command::Help => commands::handle_help(&context, &inc_command).await,
-            };
-            if let Err(why) = create_int_resp(&context, &inc_command, content).await {
-                eprintln!("Cannot respond to slash command: {}", why);
+            })
+            .create_application_command(|command| {
+                command.name("restorematch").description("Restore a deleted match (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("purgematch").description("Permanently remove a deleted match (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("restore").description("Restore this server's data from a backup (admin required)").create_option(|option| {
+                    option
+                        .name("snapshot")
+                        .description("Backup name")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("confirm")
+                        .description("Restore the backup instead of previewing the changes")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("setup").description("Setup your next match").create_option(|option| {
+                    option
+                        .name("type")
+                        .description("Series Type (if not set on the match)")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                        .add_string_choice("Best of 1", "bo1")
+                        .add_string_choice("Best of 3", "bo3")
+                        .add_string_choice("Best of 5", "bo5")
+                })
+            })
+            .create_application_command(|command| {
+                command.name("pick").description("Pick a map during the map veto").create_option(|option| {
+                    option
+                        .name("map")
+                        .description("Map name")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("ban").description("Ban a map during the map veto").create_option(|option| {
+                    option
+                        .name("map")
+                        .description("Map name")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("addmatch").description("Add match to schedule (admin required)").create_option(|option| {
+                    option
+                        .name("teamone")
+                        .description("Team 1 (Home)")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("teamtwo")
+                        .description("Team 2 (Away)")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("note")
+                        .description("Note")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("division")
+                        .description("Division")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("week")
+                        .description("Week")
+                        .kind(ApplicationCommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("editmatch").description("Edit match (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("teamone")
+                        .description("Team 1 (Home)")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("teamtwo")
+                        .description("Team 2 (Away)")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("swap")
+                        .description("Swap home and away teams")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("note")
+                        .description("Note")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("type")
+                        .description("Series Type")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                        .add_string_choice("Best of 1", "bo1")
+                        .add_string_choice("Best of 3", "bo3")
+                        .add_string_choice("Best of 5", "bo5")
+                }).create_option(|option| {
+                    option
+                        .name("date")
+                        .description("Date (Month/Day/Year)")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("time")
+                        .description("Time (include timezone) i.e. 10EST")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("state")
+                        .description("Match state")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                        .add_string_choice("Entered", "entered")
+                        .add_string_choice("Scheduled", "scheduled")
+                        .add_string_choice("Completed", "completed")
+                }).create_option(|option| {
+                    option
+                        .name("division")
+                        .description("Division")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("week")
+                        .description("Week")
+                        .kind(ApplicationCommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("force")
+                        .description("Allow changing teams of a match with a completed setup")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("clearresult")
+                        .description("Remove the recorded result, reopening the match")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("schedule").description("Schedule your next match").create_option(|option| {
+                    option
+                        .name("date")
+                        .description("Date (Month/Day/Year)")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("time")
+                        .description("Time (include timezone) i.e. 10EST")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match to schedule, defaults to your team's next open match")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("swiss").description("Show Swiss tournament standings").create_option(|option| {
+                    option
+                        .name("name")
+                        .description("Tournament name")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("bracket").description("Show a playoff bracket").create_option(|option| {
+                    option
+                        .name("name")
+                        .description("Bracket name, defaults to the latest bracket")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("seeds").description("Show the current season's team seeds")
+            })
+            .create_application_command(|command| {
+                command.name("mapstats").description("Show league wide map pick and ban rates")
+            })
+            .create_application_command(|command| {
+                command.name("teamstats").description("Show a team's map veto tendencies and results").create_option(|option| {
+                    option
+                        .name("team")
+                        .description("Team")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("h2h").description("Show the head-to-head history of two teams").create_option(|option| {
+                    option
+                        .name("team1")
+                        .description("Team one")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("team2")
+                        .description("Team two")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("cast").description("Claim a scheduled match to cast (caster role required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("stream")
+                        .description("Stream link")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                }).create_option(|option| {
+                    option
+                        .name("drop")
+                        .description("Stop casting this match")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("broadcasts").description("Show the cast matches of the next 7 days")
+            })
+            .create_application_command(|command| {
+                command.name("dispute").description("Open a dispute ticket for one of your team's matches").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("reason")
+                        .description("What the dispute is about")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("audit").description("Show the change history of a match (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("report").description("Report a match result").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("scores")
+                        .description("Map scores in order from team 1's view i.e. 13-7 9-13 13-11")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("forfeit").description("Record a forfeit (admin required)").create_option(|option| {
+                    option
+                        .name("matchid")
+                        .description("Match ID")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                }).create_option(|option| {
+                    option
+                        .name("team")
+                        .description("Forfeiting team")
+                        .kind(ApplicationCommandOptionType::Role)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("reason")
+                        .description("Reason")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                }).create_option(|option| {
+                    option
+                        .name("noshow")
+                        .description("Record as a no-show")
+                        .kind(ApplicationCommandOptionType::Boolean)
+                        .required(false)
+                })
+            })
+            .create_application_command(|command| {
+                command.name("admin").description("Admin tools (admin required)").create_option(|option| {
+                    option
+                        .name("setresult")
+                        .description("Override a match result")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("matchid")
+                                .description("Match ID")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                                .set_autocomplete(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("winner")
+                                .description("Winning team")
+                                .kind(ApplicationCommandOptionType::Role)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("reason")
+                                .description("Reason")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("newseason")
+                        .description("Start a new season and archive the current one")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("name")
+                                .description("Season name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("roundrobin")
+                        .description("Preview a round robin schedule for a division")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("division")
+                                .description("Division name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("double")
+                                .description("Double round robin (home and away)")
+                                .kind(ApplicationCommandOptionType::Boolean)
+                                .required(false)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("startweek")
+                                .description("Week number of the first round (default 1)")
+                                .kind(ApplicationCommandOptionType::Integer)
+                                .required(false)
+                                .min_int_value(1)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("type")
+                                .description("Series Type")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(false)
+                                .add_string_choice("Best of 1", "bo1")
+                                .add_string_choice("Best of 3", "bo3")
+                                .add_string_choice("Best of 5", "bo5")
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("confirmschedule")
+                        .description("Add the previewed schedule to the match list")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                }).create_option(|option| {
+                    option
+                        .name("bracket")
+                        .description("Create a playoff bracket from the current standings")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("name")
+                                .description("Bracket name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("format")
+                                .description("Bracket format")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                                .add_string_choice("Single elimination", "single")
+                                .add_string_choice("Double elimination", "double")
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("teams")
+                                .description("Number of teams from the top of the standings")
+                                .kind(ApplicationCommandOptionType::Integer)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("division")
+                                .description("Seed from this division's standings")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(false)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("type")
+                                .description("Series Type (default Best of 3)")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(false)
+                                .add_string_choice("Best of 1", "bo1")
+                                .add_string_choice("Best of 3", "bo3")
+                                .add_string_choice("Best of 5", "bo5")
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("finaltype")
+                                .description("Series Type of the finals (default Best of 5)")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(false)
+                                .add_string_choice("Best of 1", "bo1")
+                                .add_string_choice("Best of 3", "bo3")
+                                .add_string_choice("Best of 5", "bo5")
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("thirdplace")
+                                .description("Add a third place match (single elimination)")
+                                .kind(ApplicationCommandOptionType::Boolean)
+                                .required(false)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("swiss")
+                        .description("Start a Swiss tournament with a division's teams")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("name")
+                                .description("Tournament name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("division")
+                                .description("Division name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("type")
+                                .description("Series Type (default Best of 1)")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(false)
+                                .add_string_choice("Best of 1", "bo1")
+                                .add_string_choice("Best of 3", "bo3")
+                                .add_string_choice("Best of 5", "bo5")
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("resolvedispute")
+                        .description("Record the decision on a match's open dispute")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("matchid")
+                                .description("Match ID")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                                .set_autocomplete(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("outcome")
+                                .description("Outcome")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                                .add_string_choice("Upheld", "upheld")
+                                .add_string_choice("Rejected", "rejected")
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("decision")
+                                .description("Admin decision")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("swissnext")
+                        .description("Close the current Swiss round and pair the next one")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("name")
+                                .description("Tournament name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("seed")
+                        .description("Set a team's seed for the current season")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("team")
+                                .description("Team")
+                                .kind(ApplicationCommandOptionType::Role)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("seed")
+                                .description("Seed number, 0 removes the team's manual seed")
+                                .kind(ApplicationCommandOptionType::Integer)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("adddivision")
+                        .description("Add a division to the current season")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("name")
+                                .description("Division name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                }).create_option(|option| {
+                    option
+                        .name("addteam")
+                        .description("Add a team to a division of the current season")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("division")
+                                .description("Division name")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|sub_option| {
+                            sub_option
+                                .name("team")
+                                .description("Team")
+                                .kind(ApplicationCommandOptionType::Role)
+                                .required(true)
+                        })
+                })
+            })
+    }).await
+}
+
+async fn run_command(context: &Context, inc_command: &ApplicationCommandInteraction, command: &Command) -> CommandResult {
+    utils::channel_check(context, inc_command).await?;
+    let mut backup = None;
+    if let (Some(reason), Some(guild_id)) = (command.backup_reason(inc_command), inc_command.guild_id) {
+        if utils::admin_check(context, inc_command).await.is_ok() {
+            let legacy = context.data.read().await.get::<Config>().unwrap().discord.guild_id == Some(*guild_id.as_u64());
+            match utils::create_backup(Some(guild_id), legacy, &reason) {
+                Ok(name) => backup = Some(name),
+                Err(why) => eprintln!("Cannot back up before `{}`: {}", inc_command.data.name, why.internal_cause().unwrap_or_default()),
             }
         }
     }
+    let result = match command {
+        Command::Setup => commands::handle_setup(context, inc_command).await,
+        Command::Addmatch => commands::handle_add_match(context, inc_command).await,
+        Command::Deletematch => commands::handle_delete_match(context, inc_command).await,
+        Command::Editmatch => commands::handle_edit_match(context, inc_command).await,
+        Command::Schedule => commands::handle_schedule(context, inc_command).await,
+        Command::Match => commands::handle_match(context, inc_command).await,
+        Command::Matches => commands::handle_matches(context, inc_command).await,
+        Command::Maps => commands::handle_map_list(context, inc_command).await,
+        Command::Defense => commands::handle_defense_option(context, inc_command).await,
+        Command::Attack => commands::handle_attack_option(context, inc_command).await,
+        Command::Pick => commands::handle_pick_option(context, inc_command).await,
+        Command::Ban => commands::handle_ban_option(context, inc_command).await,
+        Command::Cancel => commands::handle_cancel(context, inc_command).await,
+        Command::Help => commands::handle_help(context, inc_command).await,
+        Command::Forfeit => commands::handle_forfeit(context, inc_command).await,
+        Command::Report => commands::handle_report(context, inc_command).await,
+        Command::Admin => commands::handle_admin(context, inc_command).await,
+        Command::Standings => commands::handle_standings(context, inc_command).await,
+        Command::Swiss => commands::handle_swiss_standings(context, inc_command).await,
+        Command::Bracket => commands::handle_bracket(context, inc_command).await,
+        Command::Seeds => commands::handle_seeds(context, inc_command).await,
+        Command::Mapstats => commands::handle_map_stats(context, inc_command).await,
+        Command::Teamstats => commands::handle_team_stats(context, inc_command).await,
+        Command::H2h => commands::handle_head_to_head(context, inc_command).await,
+        Command::Cast => commands::handle_cast(context, inc_command).await,
+        Command::Broadcasts => commands::handle_broadcasts(context, inc_command).await,
+        Command::Dispute => commands::handle_dispute(context, inc_command).await,
+        Command::Audit => commands::handle_audit(context, inc_command).await,
+        Command::Restorematch => commands::handle_restore_match(context, inc_command).await,
+        Command::Purgematch => commands::handle_purge_match(context, inc_command).await,
+        Command::Restore => commands::handle_restore(context, inc_command).await,
+    };
+    if let Some(backup) = backup {
+        // Commands rejected for bad input have not changed anything, so their backup is not kept
+        let cleanup = match result {
+            Err(CommandError::User(_)) | Err(CommandError::MissingOption(_)) => utils::delete_backup(&backup),
+            _ => utils::prune_backups(&backup, context.data.read().await.get::<Config>().unwrap().backups.keep),
+        };
+        if let Err(why) = cleanup {
+            eprintln!("Cannot rotate backups after `{}`: {}", inc_command.data.name, why.internal_cause().unwrap_or_default());
+        }
+    }
+    result
 }
 
-async fn create_int_resp(context: &Context, inc_command: &ApplicationCommandInteraction, content: String) -> serenity::Result<()> {
+async fn create_int_resp(context: &Context, inc_command: &ApplicationCommandInteraction, content: Response) -> serenity::Result<()> {
     return inc_command
         .create_interaction_response(&context.http, |response| {
             response
                 .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| message.content(content))
+                .interaction_response_data(|message| {
+                    message.content(content.content).embeds(content.embeds);
+                    if let Some(components) = content.components {
+                        message.set_components(components);
+                    }
+                    if content.ephemeral {
+                        message.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+                    }
+                    message
+                })
         }).await;
 }
 
@@ -404,8 +1765,19 @@ async fn create_int_resp(context: &Context, inc_command: &ApplicationCommandInte
 async fn main() {
     let config = read_config().await.unwrap();
     let token = &config.discord.token;
+    let backups = config.backups.clone();
+    if backups.interval_hours > 0 {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(backups.interval_hours * 3600)).await;
+                if let Err(why) = utils::create_backup(None, false, "scheduled").and_then(|backup| utils::prune_backups(&backup, backups.keep)) {
+                    eprintln!("Cannot create scheduled backup: {}", why.internal_cause().unwrap_or_default());
+                }
+            }
+        });
+    }
     let framework = StandardFramework::new();
-    let mut client = Client::builder(&token)
+    let mut client = Client::builder(token)
         .event_handler(Handler {})
         .framework(framework)
         .application_id(config.discord.application_id)
@@ -414,22 +1786,25 @@ async fn main() {
     {
         let mut data = client.data.write().await;
         data.insert::<Config>(config);
-        data.insert::<RiotIdCache>(read_riot_ids().await.unwrap());
+        data.insert::<RiotIdCache>(read_riot_ids().await.unwrap_or_else(|why| {
+            eprintln!("{}", why.internal_cause().unwrap_or_default());
+            std::process::exit(1);
+        }));
         data.insert::<BotState>(StateContainer { state: State::Idle });
-        data.insert::<Maps>(read_maps().await.unwrap());
-        data.insert::<Matches>(read_matches().await.unwrap());
-        data.insert::<Setup>(Setup {
-            team_one: None,
-            team_two: None,
-            maps: Vec::new(),
-            vetos: Vec::new(),
-            maps_remaining: read_maps().await.unwrap(),
-            series_type: Bo3,
-            match_id: None,
-            veto_pick_order: Vec::new(),
-            current_step: 0,
-            current_phase: State::Idle,
-        });
+        data.insert::<Maps>(read_maps().await.unwrap_or_else(|why| {
+            eprintln!("{}", why.internal_cause().unwrap_or_default());
+            std::process::exit(1);
+        }));
+        data.insert::<GuildState<Matches>>(HashMap::new());
+        data.insert::<GuildState<Seasons>>(HashMap::new());
+        data.insert::<GuildState<PendingMatches>>(HashMap::new());
+        data.insert::<GuildState<DeletedMatches>>(HashMap::new());
+        data.insert::<GuildState<Brackets>>(HashMap::new());
+        data.insert::<GuildState<SwissTournaments>>(HashMap::new());
+        data.insert::<GuildState<MatchCounter>>(HashMap::new());
+        data.insert::<GuildState<Setup>>(HashMap::new());
+        data.insert::<MatchPages>(HashMap::new());
+        data.insert::<PendingAudit>(HashMap::new());
     }
     if let Err(why) = client.start().await {
         println!("Client error: {:?}", why);
@@ -442,34 +1817,102 @@ async fn read_config() -> Result<Config, serde_yaml::Error> {
     Ok(config)
 }
 
-async fn read_riot_ids() -> Result<HashMap<u64, String>, serde_json::Error> {
-    if std::fs::read("riot_ids.json").is_ok() {
-        let json_str = std::fs::read_to_string("riot_ids.json").unwrap();
-        let json = serde_json::from_str(&json_str).unwrap();
-        Ok(json)
-    } else {
-        Ok(HashMap::new())
-    }
+async fn read_riot_ids() -> Result<HashMap<u64, String>, CommandError> {
+    read_json_file("riot_ids.json")
+}
+
+async fn read_maps() -> Result<Vec<String>, CommandError> {
+    read_json_file("maps.json")
 }
 
-async fn read_maps() -> Result<Vec<String>, serde_json::Error> {
-    if std::fs::read("maps.json").is_ok() {
-        let json_str = std::fs::read_to_string("maps.json").unwrap();
-        let json = serde_json::from_str(&json_str).unwrap();
-        Ok(json)
-    } else {
-        Ok(Vec::new())
+/// Reads a JSON data file, the default value when it does not exist yet.
+fn read_json_file<T: DeserializeOwned + Default>(path: &str) -> Result<T, CommandError> {
+    if std::fs::metadata(path).is_err() {
+        return Ok(T::default());
     }
+    std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json_str| serde_json::from_str(&json_str).map_err(|e| e.to_string()))
+        .map_err(|e| CommandError::Storage(format!("Error reading {}: {}", path, e)))
 }
 
-async fn read_matches() -> Result<Vec<Match>, serde_json::Error> {
-    if std::fs::read("matches.json").is_ok() {
-        let json_str = std::fs::read_to_string("matches.json").unwrap();
-        let json = serde_json::from_str(&json_str).unwrap();
-        Ok(json)
-    } else {
-        Ok(Vec::new())
+/// Reads a data file of a guild. The guild of a single guild install falls back to the data files
+/// in the working directory until its own files are written.
+fn read_guild_file<T: DeserializeOwned + Default>(guild_id: GuildId, name: &str, legacy: bool) -> Result<T, CommandError> {
+    let mut path = utils::guild_file(guild_id, name);
+    if legacy && std::fs::metadata(&path).is_err() {
+        path = name.to_string();
     }
+    read_json_file(&path)
 }
 
+/// Reads the matches of a guild and gives a short ID to every match that was stored before they
+/// existed. Returns whether any were given, so they can be written back.
+fn read_matches(guild_id: GuildId, legacy: bool, last_id: &mut u32) -> Result<(Vec<Match>, bool), CommandError> {
+    let mut json: Vec<Match> = read_guild_file(guild_id, "matches.json", legacy)?;
+    *last_id = json.iter().filter_map(utils::short_id_number).fold(*last_id, u32::max);
+    let mut backfilled = false;
+    for m in json.iter_mut().filter(|m| m.short_id.is_empty()) {
+        m.short_id = utils::next_short_id(last_id);
+        backfilled = true;
+    }
+    Ok((json, backfilled))
+}
 
+/// Loads the state of a guild the first time the bot sees it. Nothing is loaded when one of its
+/// data files cannot be read, so a corrupt file is never overwritten with empty data.
+async fn load_guild(context: &Context, guild_id: GuildId) -> Result<(), CommandError> {
+    let mut data = context.data.write().await;
+    if data.get::<GuildState<Setup>>().unwrap().contains_key(&guild_id) {
+        return Ok(());
+    }
+    let legacy = data.get::<Config>().unwrap().discord.guild_id == Some(*guild_id.as_u64());
+    let maps = utils::guild_maps(&data, guild_id);
+    let deleted: Vec<DeletedMatch> = read_guild_file(guild_id, "deleted_matches.json", false)?;
+    let mut last_id: u32 = read_guild_file(guild_id, "match_counter.json", false)?;
+    last_id = deleted.iter().filter_map(|d| utils::short_id_number(&d.deleted_match)).fold(last_id, u32::max);
+    let (matches, backfilled) = read_matches(guild_id, legacy, &mut last_id)?;
+    let seasons = read_guild_file(guild_id, "seasons.json", legacy)?;
+    let brackets = read_guild_file(guild_id, "brackets.json", legacy)?;
+    let swiss = read_guild_file(guild_id, "swiss.json", legacy)?;
+    if backfilled {
+        utils::write_to_file(&utils::guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(&matches)?).await?;
+        utils::write_to_file(&utils::guild_file(guild_id, "match_counter.json"), serde_json::to_string(&last_id)?).await?;
+    }
+    data.get_mut::<GuildState<Matches>>().unwrap().insert(guild_id, matches);
+    data.get_mut::<GuildState<Seasons>>().unwrap().insert(guild_id, seasons);
+    data.get_mut::<GuildState<PendingMatches>>().unwrap().insert(guild_id, Vec::new());
+    data.get_mut::<GuildState<DeletedMatches>>().unwrap().insert(guild_id, deleted);
+    data.get_mut::<GuildState<Brackets>>().unwrap().insert(guild_id, brackets);
+    data.get_mut::<GuildState<SwissTournaments>>().unwrap().insert(guild_id, swiss);
+    data.get_mut::<GuildState<MatchCounter>>().unwrap().insert(guild_id, last_id);
+    data.get_mut::<GuildState<Setup>>().unwrap().insert(guild_id, Setup {
+        team_one: None,
+        team_two: None,
+        maps: Vec::new(),
+        vetos: Vec::new(),
+        maps_remaining: maps,
+        series_type: Bo3,
+        match_id: None,
+        veto_pick_order: Vec::new(),
+        current_step: 0,
+        current_phase: State::Idle,
+        decider_side_pick: None,
+    });
+    let mut decided: Vec<(Match, DateTime<Utc>)> = data.guild::<Matches>(guild_id).iter()
+        .filter(|m| m.lobby.is_some())
+        .filter_map(|m| Some((m.clone(), m.result.as_ref()?.date_set)))
+        .collect();
+    decided.extend(data.guild::<DeletedMatches>(guild_id).iter()
+        .filter(|d| d.deleted_match.lobby.is_some())
+        .map(|d| (d.deleted_match.clone(), d.deleted_match.result.as_ref().map(|r| r.date_set).unwrap_or(d.date_deleted))));
+    let weekly_broadcasts = data.get::<Config>().unwrap().guild(guild_id).cast.schedule_channel_id.is_some();
+    drop(data);
+    if weekly_broadcasts {
+        utils::schedule_weekly_broadcasts(context, guild_id);
+    }
+    for (m, date_set) in decided {
+        utils::schedule_lobby_cleanup(context, guild_id, &m, date_set).await;
+    }
+    Ok(())
+}
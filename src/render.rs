@@ -0,0 +1,260 @@
+use font8x8::legacy::BASIC_LEGACY;
+
+use crate::{Bracket, BracketNode, BracketSide, BracketSlot, Match, ResultType};
+use crate::bracket::node_label;
+
+const GLYPH_SCALE: usize = 2;
+const GLYPH_SIZE: usize = 8 * GLYPH_SCALE;
+const NAME_CHARS: usize = 16;
+const BOX_WIDTH: usize = GLYPH_SIZE * (NAME_CHARS + 4);
+const ROW_HEIGHT: usize = GLYPH_SIZE + 8;
+const BOX_HEIGHT: usize = ROW_HEIGHT * 2;
+const COLUMN_GAP: usize = 48;
+const NODE_GAP: usize = 24;
+const MARGIN: usize = 24;
+const HEADER_HEIGHT: usize = GLYPH_SIZE + 16;
+
+const BACKGROUND: [u8; 3] = [30, 31, 34];
+const BOX_COLOR: [u8; 3] = [43, 45, 49];
+const LINE_COLOR: [u8; 3] = [110, 112, 118];
+const TEXT_COLOR: [u8; 3] = [220, 221, 222];
+const WINNER_COLOR: [u8; 3] = [87, 242, 135];
+const MUTED_COLOR: [u8; 3] = [130, 132, 138];
+
+/// A bracket node as shown to players, built from the node's `Match` when it has one.
+struct NodeView {
+    label: String,
+    match_id: String,
+    teams: [String; 2],
+    scores: [String; 2],
+    winner: Option<usize>,
+}
+
+fn slot_name(slot: &BracketSlot) -> String {
+    match slot {
+        BracketSlot::Team(team) => team.name.clone(),
+        BracketSlot::Bye => String::from("BYE"),
+        BracketSlot::Pending => String::from("TBD"),
+    }
+}
+
+fn node_view(bracket: &Bracket, node: &BracketNode, matches: &[Match]) -> NodeView {
+    let mut view = NodeView {
+        label: node_label(bracket, node),
+        match_id: String::new(),
+        teams: [slot_name(&node.slot_one), slot_name(&node.slot_two)],
+        scores: [String::new(), String::new()],
+        winner: None,
+    };
+    if let Some(winner) = &node.winner {
+        view.winner = if matches!(&node.slot_one, BracketSlot::Team(t) if t.id == winner.id) { Some(0) } else { Some(1) };
+    }
+    if let Some(m) = node.match_id.and_then(|id| matches.iter().find(|m| m.id == id)) {
+        view.match_id = m.short_id.clone();
+        view.teams = [m.team_one.name.clone(), m.team_two.name.clone()];
+        if let Some(result) = &m.result {
+            view.winner = Some(if result.winner.id == m.team_one.id { 0 } else { 1 });
+            if result.result_type == ResultType::Played {
+                let team_one_maps = result.map_scores.iter().filter(|s| s.team_one_score > s.team_two_score).count();
+                view.scores = [team_one_maps.to_string(), (result.map_scores.len() - team_one_maps).to_string()];
+            } else {
+                let winner = view.winner.unwrap();
+                view.scores[winner] = String::from("W");
+                view.scores[1 - winner] = String::from("FF");
+            }
+        }
+    }
+    view
+}
+
+/// The columns of a bracket in display order: upper rounds, lower rounds, then the finals.
+fn bracket_columns(bracket: &Bracket) -> Vec<(BracketSide, u32)> {
+    let mut columns: Vec<(BracketSide, u32)> = Vec::new();
+    for side in [BracketSide::Upper, BracketSide::Lower, BracketSide::ThirdPlace, BracketSide::GrandFinal] {
+        let mut rounds: Vec<u32> = bracket.nodes.iter().filter(|n| n.side == side).map(|n| n.round).collect();
+        rounds.sort_unstable();
+        rounds.dedup();
+        columns.extend(rounds.into_iter().map(|round| (side.clone(), round)));
+    }
+    columns
+}
+
+fn truncate(name: &str, len: usize) -> String {
+    let name: String = name.chars().filter(|c| c.is_ascii()).collect();
+    if name.len() > len { format!("{}.", &name[..len - 1]) } else { name }
+}
+
+pub(crate) fn bracket_text(bracket: &Bracket, matches: &[Match]) -> String {
+    let mut resp = format!("**{}**\n```\n", bracket.name);
+    for (side, round) in bracket_columns(bracket) {
+        let nodes: Vec<&BracketNode> = bracket.nodes.iter().filter(|n| n.side == side && n.round == round).collect();
+        let views: Vec<NodeView> = nodes.iter().map(|n| node_view(bracket, n, matches)).collect();
+        if views.iter().all(|v| v.teams.iter().any(|t| t == "BYE")) { continue; }
+        resp.push_str(format!("{}\n", views[0].label).as_str());
+        for view in views.iter().filter(|v| !v.teams.iter().any(|t| t == "BYE")) {
+            for i in 0..2 {
+                let marker = if view.winner == Some(i) { ">" } else { " " };
+                let id = if i == 0 { view.match_id.as_str() } else { "" };
+                resp.push_str(format!("{:<6}{} {:<16} {:>2}\n", id, marker, truncate(&view.teams[i], 16), view.scores[i]).as_str());
+            }
+            resp.push('\n');
+        }
+    }
+    resp.push_str("```");
+    resp
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        let pixels = BACKGROUND.iter().cloned().cycle().take(width * height * 3).collect();
+        Canvas { width, height, pixels }
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..(y + height).min(self.height) {
+            for col in x..(x + width).min(self.width) {
+                let i = (row * self.width + col) * 3;
+                self.pixels[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn line(&mut self, from: (usize, usize), to: (usize, usize), color: [u8; 3]) {
+        let (x, y) = (from.0.min(to.0), from.1.min(to.1));
+        self.fill_rect(x, y, from.0.max(to.0) - x + 2, from.1.max(to.1) - y + 2, color);
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str, color: [u8; 3]) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph = BASIC_LEGACY[(c as usize) & 0x7f];
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..8 {
+                    if bits & (1 << col) != 0 {
+                        self.fill_rect(x + i * GLYPH_SIZE + col * GLYPH_SCALE, y + row * GLYPH_SCALE, GLYPH_SCALE, GLYPH_SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Draws the bracket as a PNG with one column per round and lines connecting each match to the
+/// match its winner advances to.
+pub(crate) fn bracket_png(bracket: &Bracket, matches: &[Match]) -> Result<Vec<u8>, png::EncodingError> {
+    let columns = bracket_columns(bracket);
+    let max_nodes = columns.iter()
+        .map(|(side, round)| bracket.nodes.iter().filter(|n| n.side == *side && n.round == *round).count())
+        .max()
+        .unwrap_or(1);
+    let section_height = max_nodes * (BOX_HEIGHT + NODE_GAP);
+    let width = MARGIN * 2 + columns.len() * (BOX_WIDTH + COLUMN_GAP) - COLUMN_GAP;
+    let height = MARGIN * 2 + HEADER_HEIGHT + section_height;
+    let mut positions: Vec<Option<(usize, usize)>> = vec![None; bracket.nodes.len()];
+    for (column, (side, round)) in columns.iter().enumerate() {
+        let nodes: Vec<&BracketNode> = bracket.nodes.iter().filter(|n| n.side == *side && n.round == *round).collect();
+        for (i, node) in nodes.iter().enumerate() {
+            let center = section_height * (2 * i + 1) / (2 * nodes.len());
+            positions[node.id] = Some((MARGIN + column * (BOX_WIDTH + COLUMN_GAP), MARGIN + HEADER_HEIGHT + center - BOX_HEIGHT / 2));
+        }
+    }
+    let mut canvas = Canvas::new(width, height);
+    for node in &bracket.nodes {
+        if let (Some((x, y)), Some((target, _))) = (positions[node.id], node.winner_to) {
+            if let Some((target_x, target_y)) = positions[target] {
+                if target_x <= x { continue; }
+                let middle_x = target_x - COLUMN_GAP / 2;
+                canvas.line((x + BOX_WIDTH, y + ROW_HEIGHT), (middle_x, y + ROW_HEIGHT), LINE_COLOR);
+                canvas.line((middle_x, y + ROW_HEIGHT), (middle_x, target_y + ROW_HEIGHT), LINE_COLOR);
+                canvas.line((middle_x, target_y + ROW_HEIGHT), (target_x, target_y + ROW_HEIGHT), LINE_COLOR);
+            }
+        }
+    }
+    for (column, (side, round)) in columns.iter().enumerate() {
+        if let Some(node) = bracket.nodes.iter().find(|n| n.side == *side && n.round == *round) {
+            let label = truncate(&node_label(bracket, node), NAME_CHARS + 4);
+            canvas.text(MARGIN + column * (BOX_WIDTH + COLUMN_GAP), MARGIN, &label, MUTED_COLOR);
+        }
+    }
+    for node in &bracket.nodes {
+        let (x, y) = positions[node.id].unwrap();
+        let view = node_view(bracket, node, matches);
+        canvas.fill_rect(x, y, BOX_WIDTH, BOX_HEIGHT, BOX_COLOR);
+        for i in 0..2 {
+            let color = match view.winner {
+                Some(winner) if winner == i => WINNER_COLOR,
+                Some(_) => MUTED_COLOR,
+                None => TEXT_COLOR,
+            };
+            let row_y = y + i * ROW_HEIGHT + (ROW_HEIGHT - GLYPH_SIZE) / 2;
+            canvas.text(x + 4, row_y, &truncate(&view.teams[i], NAME_CHARS), color);
+            canvas.text(x + BOX_WIDTH - 4 - GLYPH_SIZE * 2, row_y, &format!("{:>2}", view.scores[i]), color);
+        }
+    }
+    canvas.encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serenity::model::prelude::{GuildId, RoleId, UserId};
+
+    use super::*;
+    use crate::{MapScore, MatchResult, RolePartial, SeriesType};
+    use crate::bracket::{build_single_elimination, create_bracket_matches};
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn decide(m: &mut Match, winner: &RolePartial, result_type: ResultType, map_scores: Vec<(u32, u32)>) {
+        let map_scores = map_scores.into_iter().map(|(team_one_score, team_two_score)| MapScore { map: String::from("Ascent"), team_one_score, team_two_score }).collect();
+        m.result = Some(MatchResult { winner: winner.clone(), result_type, map_scores, reason: None, set_by: UserId(1), date_set: Utc::now() });
+    }
+
+    #[test]
+    fn text_tree_shows_scores_and_winners_and_hides_byes() {
+        let seeds: Vec<RolePartial> = (1..=5).map(team).collect();
+        let mut bracket = build_single_elimination(String::from("Playoffs"), None, &seeds, &SeriesType::Bo3, &SeriesType::Bo3, false);
+        let mut matches = Vec::new();
+        create_bracket_matches(&mut bracket, &mut matches, &mut 0, false);
+        decide(&mut matches[0], &team(5), ResultType::Played, vec![(13, 9), (7, 13), (11, 13)]);
+        decide(&mut matches[1], &team(3), ResultType::Forfeit, Vec::new());
+        let lines: Vec<&str> = vec![
+            "**Playoffs**", "```",
+            "Round 1",
+            "M-01    Team 4            1",
+            "      > Team 5            2",
+            "",
+            "Semifinal",
+            "        Team 1             ",
+            "        TBD                ",
+            "",
+            "M-02    Team 2           FF",
+            "      > Team 3            W",
+            "",
+            "Final",
+            "        TBD                ",
+            "        TBD                ",
+            "", "```",
+        ];
+        assert_eq!(bracket_text(&bracket, &matches), lines.join("\n"));
+    }
+}
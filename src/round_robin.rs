@@ -0,0 +1,110 @@
+use crate::RolePartial;
+
+/// Builds the rounds of a round robin with the circle method. The first team in each
+/// pairing is the home team; home/away alternates so every team hosts about half its
+/// matches. A double round robin repeats every round with home and away swapped.
+pub(crate) fn round_robin_rounds(teams: &[RolePartial], double: bool) -> Vec<Vec<(RolePartial, RolePartial)>> {
+    let mut slots: Vec<Option<RolePartial>> = teams.iter().cloned().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.insert(0, None);
+    }
+    let slot_count = slots.len();
+    let mut rounds = Vec::new();
+    for round in 0..slot_count.saturating_sub(1) {
+        let mut pairings = Vec::new();
+        for i in 0..slot_count / 2 {
+            let mut home = &slots[i];
+            let mut away = &slots[slot_count - 1 - i];
+            let swap = if i == 0 { round % 2 == 1 } else { i % 2 == 1 };
+            if swap {
+                std::mem::swap(&mut home, &mut away);
+            }
+            if let (Some(home), Some(away)) = (home, away) {
+                pairings.push((home.clone(), away.clone()));
+            }
+        }
+        rounds.push(pairings);
+        let last = slots.pop().unwrap();
+        slots.insert(1, last);
+    }
+    if double {
+        let return_rounds: Vec<Vec<(RolePartial, RolePartial)>> = rounds.iter()
+            .map(|pairings| pairings.iter().map(|(home, away)| (away.clone(), home.clone())).collect())
+            .collect();
+        rounds.extend(return_rounds);
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+    use serenity::model::prelude::{GuildId, RoleId};
+
+    use super::*;
+
+    fn teams(count: u64) -> Vec<RolePartial> {
+        (1..=count).map(|id| RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn every_pair_plays_once_per_cycle(count in 2u64..13, double in any::<bool>()) {
+            let rounds = round_robin_rounds(&teams(count), double);
+            let cycle_rounds = if count % 2 == 0 { count - 1 } else { count } as usize;
+            prop_assert_eq!(rounds.len(), if double { cycle_rounds * 2 } else { cycle_rounds });
+            let mut fixtures: HashMap<(u64, u64), u32> = HashMap::new();
+            for pairings in &rounds {
+                let mut playing: Vec<u64> = pairings.iter().flat_map(|(home, away)| vec![home.id.0, away.id.0]).collect();
+                playing.sort_unstable();
+                playing.dedup();
+                prop_assert_eq!(playing.len(), pairings.len() * 2);
+                for (home, away) in pairings {
+                    *fixtures.entry((home.id.0, away.id.0)).or_insert(0) += 1;
+                }
+            }
+            for one in 1..=count {
+                for two in (one + 1)..=count {
+                    let home = fixtures.get(&(one, two)).cloned().unwrap_or(0);
+                    let away = fixtures.get(&(two, one)).cloned().unwrap_or(0);
+                    if double {
+                        prop_assert!(home == 1 && away == 1);
+                    } else {
+                        prop_assert_eq!(home + away, 1);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn every_team_hosts_about_half_its_matches(count in 2u64..13) {
+            let mut home_away: HashMap<u64, (i32, i32)> = HashMap::new();
+            for (home, away) in round_robin_rounds(&teams(count), false).iter().flatten() {
+                home_away.entry(home.id.0).or_insert((0, 0)).0 += 1;
+                home_away.entry(away.id.0).or_insert((0, 0)).1 += 1;
+            }
+            for (team, (home, away)) in home_away {
+                prop_assert!((home - away).abs() <= 1, "team {} hosts {} and visits {}", team, home, away);
+            }
+        }
+
+        #[test]
+        fn byes_rotate_through_every_team(count in (1u64..7).prop_map(|n| n * 2 + 1)) {
+            let all = teams(count);
+            let rounds = round_robin_rounds(&all, false);
+            let mut byes = Vec::new();
+            for pairings in &rounds {
+                let resting: Vec<u64> = all.iter()
+                    .filter(|t| !pairings.iter().any(|(home, away)| home.id == t.id || away.id == t.id))
+                    .map(|t| t.id.0)
+                    .collect();
+                prop_assert_eq!(resting.len(), 1);
+                byes.extend(resting);
+            }
+            byes.sort_unstable();
+            prop_assert_eq!(byes, (1..=count).collect::<Vec<_>>());
+        }
+    }
+}
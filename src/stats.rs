@@ -0,0 +1,175 @@
+use serenity::model::prelude::RoleId;
+
+use crate::{Match, MapStat, TeamStats};
+use crate::StepType::{Pick, Veto};
+
+fn map_entry<'a>(stats: &'a mut Vec<MapStat>, map: &str) -> &'a mut MapStat {
+    let index = match stats.iter().position(|s| s.map.eq_ignore_ascii_case(map)) {
+        Some(index) => index,
+        None => {
+            stats.push(MapStat { map: map.to_lowercase(), ..Default::default() });
+            stats.len() - 1
+        }
+    };
+    &mut stats[index]
+}
+
+/// Pick, ban and play counts of every map across all completed setups. Returns the number of
+/// setups alongside the stats so rates can be computed.
+pub(crate) fn league_map_stats(matches: &[Match]) -> (u32, Vec<MapStat>) {
+    let mut setups = 0;
+    let mut stats: Vec<MapStat> = Vec::new();
+    for setup_info in matches.iter().filter_map(|m| m.setup_info.as_ref()) {
+        setups += 1;
+        for step in &setup_info.vetos {
+            if let Some(map) = &step.map {
+                let entry = map_entry(&mut stats, map);
+                if step.step_type == Veto { entry.bans += 1; } else { entry.picks += 1; }
+            }
+        }
+        for series_map in &setup_info.maps {
+            map_entry(&mut stats, &series_map.map).played += 1;
+        }
+    }
+    stats.sort_by(|a, b| b.played.cmp(&a.played).then(b.picks.cmp(&a.picks)).then(a.bans.cmp(&b.bans)));
+    (setups, stats)
+}
+
+/// Veto tendencies and per map results of a single team. Map wins come from reported map scores
+/// and starting sides from the side picks of each setup.
+pub(crate) fn team_map_stats(matches: &[Match], team: RoleId) -> TeamStats {
+    let mut team_stats = TeamStats { setups: 0, maps: Vec::new(), first_bans: Vec::new() };
+    for m in matches.iter().filter(|m| m.team_one.id == team || m.team_two.id == team) {
+        let map_won = |map: &str| m.result.as_ref()
+            .and_then(|r| r.map_scores.iter().find(|s| s.map.eq_ignore_ascii_case(map)))
+            .map(|s| if m.team_one.id == team { s.team_one_score > s.team_two_score } else { s.team_two_score > s.team_one_score });
+        if let Some(setup_info) = &m.setup_info {
+            team_stats.setups += 1;
+            for step in setup_info.vetos.iter().filter(|v| v.team.id == team) {
+                if let Some(map) = &step.map {
+                    let entry = map_entry(&mut team_stats.maps, map);
+                    if step.step_type == Pick { entry.picks += 1; } else { entry.bans += 1; }
+                }
+            }
+            let first_ban = setup_info.vetos.iter().find(|v| v.team.id == team && v.step_type == Veto).and_then(|v| v.map.clone());
+            if let Some(map) = first_ban {
+                match team_stats.first_bans.iter_mut().find(|b| b.0.eq_ignore_ascii_case(&map)) {
+                    Some(first_ban) => first_ban.1 += 1,
+                    None => team_stats.first_bans.push((map.to_lowercase(), 1)),
+                }
+            }
+            for series_map in &setup_info.maps {
+                let won = map_won(&series_map.map) == Some(true);
+                let entry = map_entry(&mut team_stats.maps, &series_map.map);
+                if series_map.start_attack.as_ref().is_some_and(|t| t.id == team) {
+                    entry.attack_starts += 1;
+                    if won { entry.attack_wins += 1; }
+                } else if series_map.start_defense.as_ref().is_some_and(|t| t.id == team) {
+                    entry.defense_starts += 1;
+                    if won { entry.defense_wins += 1; }
+                }
+            }
+        }
+        if let Some(result) = &m.result {
+            for score in &result.map_scores {
+                let won = map_won(&score.map) == Some(true);
+                let entry = map_entry(&mut team_stats.maps, &score.map);
+                entry.played += 1;
+                if won { entry.wins += 1; }
+            }
+        }
+    }
+    team_stats.maps.sort_by(|a, b| b.bans.cmp(&a.bans).then(b.picks.cmp(&a.picks)));
+    team_stats.first_bans.sort_by_key(|b| std::cmp::Reverse(b.1));
+    team_stats
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serenity::model::prelude::{GuildId, UserId};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{MapScore, MatchResult, MatchState, ResultType, RolePartial, SeriesMap, SeriesType, SetupInfo, SetupStep, StepType};
+    use crate::utils::print_map_stats;
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn step(step_type: StepType, team: &RolePartial, map: &str) -> SetupStep {
+        SetupStep { step_type, team: team.clone(), map: Some(map.to_string()) }
+    }
+
+    /// A Bo1 between `team_one` and `team_two` played on the last map of `vetos`, which `winner`
+    /// won 13-7 after `attacker` started on attack.
+    fn played(team_one: &RolePartial, team_two: &RolePartial, vetos: Vec<SetupStep>, attacker: &RolePartial, winner: &RolePartial) -> Match {
+        let last = vetos.last().unwrap();
+        let map = last.map.clone().unwrap();
+        let defender = if attacker.id == team_one.id { team_two } else { team_one };
+        let score = if winner.id == team_one.id { (13, 7) } else { (7, 13) };
+        Match {
+            id: Uuid::new_v4(),
+            short_id: String::new(),
+            team_one: team_one.clone(),
+            team_two: team_two.clone(),
+            note: None,
+            date_added: Utc::now(),
+            match_state: MatchState::Completed,
+            schedule_info: None,
+            setup_info: Some(SetupInfo {
+                series_type: SeriesType::Bo1,
+                maps: vec![SeriesMap { map: map.clone(), picked_by: last.team.clone(), start_attack: Some(attacker.clone()), start_defense: Some(defender.clone()) }],
+                vetos,
+            }),
+            result: Some(MatchResult {
+                winner: winner.clone(),
+                result_type: ResultType::Played,
+                map_scores: vec![MapScore { map, team_one_score: score.0, team_two_score: score.1 }],
+                reason: None,
+                set_by: UserId(1),
+                date_set: Utc::now(),
+            }),
+            series_type: Some(SeriesType::Bo1),
+            season: None,
+            division: None,
+            week: None,
+            thread_id: None,
+            lobby: None,
+            casters: Vec::new(),
+            disputes: Vec::new(),
+        }
+    }
+
+    fn matches() -> Vec<Match> {
+        let (t1, t2, t3) = (team(1), team(2), team(3));
+        vec![
+            played(&t1, &t2, vec![step(Veto, &t1, "Ascent"), step(Veto, &t2, "Bind"), step(Pick, &t1, "Haven")], &t2, &t1),
+            played(&t2, &t1, vec![step(Veto, &t2, "ascent"), step(Veto, &t1, "Split"), step(Pick, &t2, "Bind")], &t1, &t2),
+            played(&t3, &t2, vec![step(Veto, &t3, "Split"), step(Veto, &t2, "Ascent"), step(Pick, &t3, "Haven")], &t3, &t2),
+        ]
+    }
+
+    #[test]
+    fn league_rates_count_every_setup() {
+        let (setups, stats) = league_map_stats(&matches());
+        assert_eq!(setups, 3);
+        let counts: Vec<(&str, u32, u32, u32)> = stats.iter().map(|s| (s.map.as_str(), s.picks, s.bans, s.played)).collect();
+        assert_eq!(counts, vec![("haven", 2, 0, 2), ("bind", 1, 1, 1), ("split", 0, 2, 0), ("ascent", 0, 3, 0)]);
+        let printed = print_map_stats(setups, &stats);
+        assert!(printed.contains("HAVEN        66%    0%       2\n"));
+        assert!(printed.contains("ASCENT        0%  100%       0\n"));
+    }
+
+    #[test]
+    fn team_stats_only_count_the_team_own_steps_and_maps() {
+        let stats = team_map_stats(&matches(), RoleId(2));
+        assert_eq!(stats.setups, 3);
+        let counts: Vec<(&str, u32, u32, u32, u32)> = stats.maps.iter().map(|s| (s.map.as_str(), s.picks, s.bans, s.played, s.wins)).collect();
+        assert_eq!(counts, vec![("ascent", 0, 2, 0, 0), ("bind", 1, 1, 1, 1), ("haven", 0, 0, 2, 1)]);
+        assert_eq!(stats.first_bans, vec![(String::from("ascent"), 2), (String::from("bind"), 1)]);
+        let haven = stats.maps.iter().find(|s| s.map == "haven").unwrap();
+        assert_eq!((haven.attack_starts, haven.attack_wins, haven.defense_starts, haven.defense_wins), (1, 0, 1, 1));
+    }
+}
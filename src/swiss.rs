@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use serenity::model::prelude::RoleId;
+
+use crate::{Match, RolePartial, SwissRecord, SwissTournament};
+
+const MAX_PAIRING_ATTEMPTS: u32 = 100_000;
+
+fn tournament_matches<'a>(tournament: &SwissTournament, matches: &'a [Match]) -> Vec<&'a Match> {
+    matches.iter()
+        .filter(|m| tournament.rounds.iter().any(|round| round.contains(&m.id)))
+        .collect()
+}
+
+/// Records of every team in the tournament, sorted by wins then Buchholz score (the sum of the
+/// wins of every opponent faced). Byes count as a win without an opponent.
+pub(crate) fn swiss_records(tournament: &SwissTournament, matches: &[Match]) -> Vec<SwissRecord> {
+    let played = tournament_matches(tournament, matches);
+    let mut records: Vec<SwissRecord> = tournament.teams.iter()
+        .map(|team| SwissRecord { team: team.clone(), wins: 0, losses: 0, buchholz: 0 })
+        .collect();
+    for record in records.iter_mut() {
+        let team_id = record.team.id;
+        record.wins = tournament.byes.iter().filter(|t| t.id == team_id).count() as u32;
+        for m in played.iter().filter(|m| m.team_one.id == team_id || m.team_two.id == team_id) {
+            if let Some(result) = &m.result {
+                if result.winner.id == team_id { record.wins += 1; } else { record.losses += 1; }
+            }
+        }
+    }
+    let wins: Vec<(RoleId, u32)> = records.iter().map(|r| (r.team.id, r.wins)).collect();
+    for record in records.iter_mut() {
+        record.buchholz = played.iter()
+            .filter(|m| m.result.is_some())
+            .filter_map(|m| if m.team_one.id == record.team.id {
+                Some(m.team_two.id)
+            } else if m.team_two.id == record.team.id {
+                Some(m.team_one.id)
+            } else {
+                None
+            })
+            .map(|opponent| wins.iter().find(|w| w.0 == opponent).map(|w| w.1).unwrap_or(0))
+            .sum();
+    }
+    let seed = |team: &RolePartial| tournament.teams.iter().position(|t| t.id == team.id).unwrap_or(usize::MAX);
+    records.sort_by(|a, b| b.wins.cmp(&a.wins)
+        .then(b.buchholz.cmp(&a.buchholz))
+        .then(seed(&a.team).cmp(&seed(&b.team))));
+    records
+}
+
+fn pair_teams(order: &[usize], wins: &[u32], played: &HashSet<(usize, usize)>, allow_rematch: bool, attempts: &mut u32) -> Option<Vec<(usize, usize)>> {
+    if order.is_empty() {
+        return Some(Vec::new());
+    }
+    *attempts += 1;
+    if *attempts > MAX_PAIRING_ATTEMPTS {
+        return None;
+    }
+    let first = order[0];
+    let rest = &order[1..];
+    // Within a score group the top half plays the bottom half
+    let mut same_score: Vec<usize> = rest.iter().cloned().filter(|t| wins[*t] == wins[first]).collect();
+    let half = same_score.len() / 2;
+    same_score.rotate_left(half);
+    let candidates = same_score.into_iter().chain(rest.iter().cloned().filter(|t| wins[*t] != wins[first]));
+    for candidate in candidates {
+        if !allow_rematch && played.contains(&(first.min(candidate), first.max(candidate))) { continue; }
+        let remaining: Vec<usize> = rest.iter().cloned().filter(|t| *t != candidate).collect();
+        if let Some(mut pairings) = pair_teams(&remaining, wins, played, allow_rematch, attempts) {
+            pairings.insert(0, (first, candidate));
+            return Some(pairings);
+        }
+    }
+    None
+}
+
+/// Pairs the next round by record while avoiding rematches where possible. With an odd number of
+/// teams the lowest ranked team without a bye sits out. The higher ranked team is listed first.
+pub(crate) fn pair_next_round(tournament: &SwissTournament, matches: &[Match]) -> (Vec<(RolePartial, RolePartial)>, Option<RolePartial>) {
+    let mut records = swiss_records(tournament, matches);
+    let mut bye = None;
+    if records.len() % 2 == 1 {
+        let bye_index = records.iter()
+            .rposition(|r| !tournament.byes.iter().any(|t| t.id == r.team.id))
+            .unwrap_or(records.len() - 1);
+        bye = Some(records.remove(bye_index).team);
+    }
+    let wins: Vec<u32> = records.iter().map(|r| r.wins).collect();
+    let mut played = HashSet::new();
+    for m in tournament_matches(tournament, matches) {
+        let one = records.iter().position(|r| r.team.id == m.team_one.id);
+        let two = records.iter().position(|r| r.team.id == m.team_two.id);
+        if let (Some(one), Some(two)) = (one, two) {
+            played.insert((one.min(two), one.max(two)));
+        }
+    }
+    let order: Vec<usize> = (0..records.len()).collect();
+    let mut attempts = 0;
+    let pairings = pair_teams(&order, &wins, &played, false, &mut attempts)
+        .or_else(|| pair_teams(&order, &wins, &played, true, &mut 0))
+        .unwrap_or_default();
+    let pairings = pairings.into_iter()
+        .map(|(one, two)| (records[one].team.clone(), records[two].team.clone()))
+        .collect();
+    (pairings, bye)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use proptest::prelude::*;
+    use serenity::model::prelude::{GuildId, UserId};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{MatchResult, MatchState, ResultType, SeriesType};
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn new_tournament(teams: Vec<RolePartial>) -> SwissTournament {
+        SwissTournament { name: String::from("Qualifier"), season: None, teams, series_type: SeriesType::Bo1, rounds: Vec::new(), byes: Vec::new() }
+    }
+
+    fn played(team_one: &RolePartial, team_two: &RolePartial, winner: &RolePartial) -> Match {
+        Match {
+            id: Uuid::new_v4(),
+            short_id: String::new(),
+            team_one: team_one.clone(),
+            team_two: team_two.clone(),
+            note: None,
+            date_added: Utc::now(),
+            match_state: MatchState::Completed,
+            schedule_info: None,
+            setup_info: None,
+            result: Some(MatchResult { winner: winner.clone(), result_type: ResultType::Played, map_scores: Vec::new(), reason: None, set_by: UserId(1), date_set: Utc::now() }),
+            series_type: Some(SeriesType::Bo1),
+            season: None,
+            division: None,
+            week: None,
+            thread_id: None,
+            lobby: None,
+            casters: Vec::new(),
+            disputes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn buchholz_breaks_ties_before_seed() {
+        let (t1, t2, t3, t4) = (team(1), team(2), team(3), team(4));
+        let mut tournament = new_tournament(vec![t1.clone(), t2.clone(), t4.clone(), t3.clone()]);
+        let matches = vec![played(&t1, &t2, &t1), played(&t3, &t4, &t3), played(&t1, &t3, &t1), played(&t4, &t2, &t4)];
+        tournament.rounds = vec![vec![matches[0].id, matches[1].id], vec![matches[2].id, matches[3].id]];
+        let records = swiss_records(&tournament, &matches);
+        let order: Vec<(u64, u32, u32)> = records.iter().map(|r| (r.team.id.0, r.wins, r.buchholz)).collect();
+        assert_eq!(order, vec![(1, 2, 1), (3, 1, 3), (4, 1, 1), (2, 0, 3)]);
+    }
+
+    proptest! {
+        #[test]
+        fn rounds_never_repeat_a_pairing(count in 4u64..13, winners in proptest::collection::vec(any::<bool>(), 36)) {
+            let mut tournament = new_tournament((1..=count).map(team).collect());
+            let mut matches: Vec<Match> = Vec::new();
+            let mut winners = winners.into_iter();
+            for _ in 0..(count - 1) / 2 {
+                let (pairings, bye) = pair_next_round(&tournament, &matches);
+                prop_assert_eq!(pairings.len() as u64, count / 2);
+                prop_assert_eq!(bye.is_some(), count % 2 == 1);
+                let records = swiss_records(&tournament, &matches);
+                let rank = |t: &RolePartial| records.iter().position(|r| r.team.id == t.id).unwrap();
+                let mut round = Vec::new();
+                for (one, two) in &pairings {
+                    prop_assert!(rank(one) < rank(two));
+                    prop_assert!(!matches.iter().any(|m| (m.team_one.id == one.id && m.team_two.id == two.id) || (m.team_one.id == two.id && m.team_two.id == one.id)));
+                    let winner = if winners.next().unwrap() { one } else { two };
+                    let m = played(one, two, winner);
+                    round.push(m.id);
+                    matches.push(m);
+                }
+                if let Some(team) = bye {
+                    prop_assert!(!tournament.byes.iter().any(|t| t.id == team.id));
+                    tournament.byes.push(team);
+                }
+                tournament.rounds.push(round);
+            }
+            for records in swiss_records(&tournament, &matches).windows(2) {
+                prop_assert!((records[0].wins, records[0].buchholz) >= (records[1].wins, records[1].buchholz));
+            }
+        }
+    }
+}
@@ -1,19 +1,252 @@
-use serenity::model::prelude::{GuildContainer, Role, RoleId, User};
-use serenity::model::prelude::application_command::{ApplicationCommandInteraction};
-use serenity::prelude::Context;
-use serenity::utils::MessageBuilder;
-use crate::{Bo3, Config, Maps, Match, Matches, RolePartial, Setup, SetupInfo, State};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serenity::model::prelude::{Channel, ChannelId, ChannelType, GuildContainer, GuildId, PermissionOverwrite, PermissionOverwriteType, Permissions, Role, RoleId, User, UserId};
+use serenity::model::prelude::application_command::{ApplicationCommandInteraction, ApplicationCommandInteractionDataOption, ApplicationCommandInteractionDataOptionValue};
+use serenity::prelude::{Context, TypeMap};
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::interactions::message_component::ButtonStyle;
+use serenity::utils::{Colour, MessageBuilder};
+use crate::{AuditEntry, AuditSnapshot, Bo3, Brackets, CommandError, Config, DeletedMatch, DeletedMatches, Division, GuildData, MapStat, Maps, Match, MatchCounter, MatchFilter, MatchLobby, Matches, PendingAudit, read_json_file, ResultType, RolePartial, Season, Seasons, Setup, SetupInfo, Standing, State, SwissTournaments, TeamStats};
+use crate::audit::{audit_entries, command_line, print_audit_entry};
+use crate::bracket::advance_bracket;
 use crate::MatchState::Completed;
 use crate::StepType::Veto;
+use crate::veto::setup_summary;
+use uuid::Uuid;
+
+const BACKUP_DIR: &str = "backups";
+
+pub(crate) async fn write_to_file(path: &str, content: String) -> Result<(), CommandError> {
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| CommandError::Storage(format!("Error creating {}: {}", dir.display(), e)))?;
+    }
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, content)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| CommandError::Storage(format!("Error writing to {}: {}", path, e)))
+}
+
+/// Data files of a guild that are backed up and restored together.
+const GUILD_DATA_FILES: [&str; 6] = ["matches.json", "seasons.json", "brackets.json", "swiss.json", "deleted_matches.json", "match_counter.json"];
+
+/// Data files copied into a backup. Scheduled backups hold the shared map pool and Riot IDs plus
+/// the data of every guild, including that of a single guild install still in the working
+/// directory. Backups taken for one guild only hold its own data.
+fn backup_files(guild: Option<GuildId>, legacy: bool) -> Vec<String> {
+    let mut files = Vec::new();
+    match guild {
+        Some(guild_id) => {
+            files.extend(GUILD_DATA_FILES.iter().map(|name| guild_file(guild_id, name)));
+            if legacy {
+                files.extend(GUILD_DATA_FILES.iter().map(|name| name.to_string()));
+            }
+        }
+        None => {
+            files.extend([String::from("maps.json"), String::from("riot_ids.json")]);
+            files.extend(GUILD_DATA_FILES.iter().map(|name| name.to_string()));
+            if let Ok(guilds) = std::fs::read_dir("guilds") {
+                for guild in guilds.flatten() {
+                    files.extend(GUILD_DATA_FILES.iter().map(|name| format!("guilds/{}/{}", guild.file_name().to_string_lossy(), name)));
+                }
+            }
+        }
+    }
+    files.retain(|file| Path::new(file).is_file());
+    files
+}
+
+/// Copies the data files into `backups/<timestamp>-<reason>/`, or only the data of `guild` into
+/// `backups/<timestamp>-<guild id>-<reason>/`. Returns the name of the new backup.
+pub(crate) fn create_backup(guild: Option<GuildId>, legacy: bool, reason: &str) -> Result<String, CommandError> {
+    let reason = match guild {
+        Some(guild_id) => format!("{}-{}", guild_id, reason),
+        None => reason.to_string(),
+    };
+    let name = format!("{}-{}", Utc::now().format("%Y%m%d-%H%M%S"), reason);
+    let dir = Path::new(BACKUP_DIR).join(&name);
+    std::fs::create_dir_all(&dir)?;
+    for file in backup_files(guild, legacy) {
+        let target = dir.join(&file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(&file, &target)?;
+    }
+    Ok(name)
+}
+
+/// Removes the oldest backups taken for the same reason and guild as `backup` beyond `keep`, so
+/// backups before busy commands never push out the scheduled ones.
+pub(crate) fn prune_backups(backup: &str, keep: usize) -> Result<(), CommandError> {
+    let reason = backup_reason(backup);
+    for old in list_backups().iter().filter(|b| backup_reason(b) == reason).skip(keep.max(1)) {
+        std::fs::remove_dir_all(Path::new(BACKUP_DIR).join(old))?;
+    }
+    Ok(())
+}
+
+/// Removes a backup that is no longer needed, such as one taken before a command that was rejected.
+pub(crate) fn delete_backup(backup: &str) -> Result<(), CommandError> {
+    Ok(std::fs::remove_dir_all(Path::new(BACKUP_DIR).join(backup))?)
+}
+
+/// The part of a backup name after the timestamp, e.g. `scheduled` or `<guild id>-deletematch`.
+fn backup_reason(backup: &str) -> &str {
+    backup.splitn(3, '-').nth(2).unwrap_or_default()
+}
+
+/// The guild a backup was taken for, `None` for backups of every guild.
+fn backup_guild(backup: &str) -> Option<GuildId> {
+    backup_reason(backup).split('-').next().and_then(|id| id.parse().ok()).map(GuildId)
+}
+
+/// Names of all backups, newest first.
+fn list_backups() -> Vec<String> {
+    let mut backups: Vec<String> = std::fs::read_dir(BACKUP_DIR)
+        .map(|entries| entries.flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect())
+        .unwrap_or_default();
+    backups.sort_by(|a, b| b.cmp(a));
+    backups
+}
+
+/// Names of the backups a guild can restore from, newest first: its own backups and the backups
+/// of every guild that hold its matches.
+pub(crate) fn guild_backups(guild_id: GuildId, legacy: bool) -> Vec<String> {
+    list_backups().into_iter()
+        .filter(|b| match backup_guild(b) {
+            Some(id) => id == guild_id,
+            None => backup_file(b, &guild_file(guild_id, "matches.json")).is_some() || (legacy && backup_file(b, "matches.json").is_some()),
+        })
+        .collect()
+}
+
+/// Path of a data file inside a backup, `None` when the backup does not contain it.
+fn backup_file(backup: &str, file: &str) -> Option<PathBuf> {
+    Some(Path::new(BACKUP_DIR).join(backup).join(file)).filter(|path| path.is_file())
+}
+
+/// One of a guild's data files as it was when the backup was taken, `None` when the backup does
+/// not contain it.
+pub(crate) fn read_backup_file<T: DeserializeOwned + Default>(backup: &str, guild_id: GuildId, name: &str, legacy: bool) -> Result<Option<T>, CommandError> {
+    let path = backup_file(backup, &guild_file(guild_id, name))
+        .or_else(|| if legacy { backup_file(backup, name) } else { None });
+    match path {
+        Some(path) => Ok(Some(read_json_file(&path.to_string_lossy())?)),
+        None => Ok(None),
+    }
+}
+
+/// The matches of a guild as they were when the backup was taken.
+pub(crate) fn read_backup_matches(backup: &str, guild_id: GuildId, legacy: bool) -> Result<Vec<Match>, CommandError> {
+    read_backup_file(backup, guild_id, "matches.json", legacy)?
+        .ok_or_else(|| CommandError::User(format!("Snapshot `{}` has no matches for this server", backup)))
+}
+
+/// Path of one of a guild's data files.
+pub(crate) fn guild_file(guild_id: GuildId, name: &str) -> String {
+    format!("guilds/{}/{}", guild_id, name)
+}
+
+pub(crate) fn find_option<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Option<&'a ApplicationCommandInteractionDataOptionValue> {
+    options.iter()
+        .find(|o| o.name == name)
+        .and_then(|o| o.resolved.as_ref())
+}
+
+/// A week number option such as `week`, which must be at least 1.
+pub(crate) fn week_option(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Result<Option<u32>, CommandError> {
+    match find_option(options, name) {
+        Some(ApplicationCommandInteractionDataOptionValue::Integer(week)) if (1..=u32::MAX as i64).contains(week) => Ok(Some(*week as u32)),
+        Some(ApplicationCommandInteractionDataOptionValue::Integer(_)) => Err(CommandError::User(format!("`{}` must be 1 or higher", name))),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn required_option<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Result<&'a ApplicationCommandInteractionDataOptionValue, CommandError> {
+    find_option(options, name).ok_or_else(|| CommandError::MissingOption(name.to_string()))
+}
+
+pub(crate) fn required_string<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Result<&'a String, CommandError> {
+    match required_option(options, name)? {
+        ApplicationCommandInteractionDataOptionValue::String(value) => Ok(value),
+        _ => Err(CommandError::MissingOption(name.to_string())),
+    }
+}
+
+pub(crate) fn required_role(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Result<RolePartial, CommandError> {
+    match required_option(options, name)? {
+        ApplicationCommandInteractionDataOptionValue::Role(role) => Ok(RolePartial { id: role.id, name: role.name.to_string(), guild_id: role.guild_id }),
+        _ => Err(CommandError::MissingOption(name.to_string())),
+    }
+}
+
+pub(crate) fn guild_id(msg: &ApplicationCommandInteraction) -> Result<GuildId, CommandError> {
+    msg.guild_id.ok_or(CommandError::NotInGuild)
+}
+
+pub(crate) fn is_match_id(m: &Match, match_id: &str) -> bool {
+    let match_id = match_id.trim();
+    m.short_id.eq_ignore_ascii_case(match_id) || Uuid::from_str(match_id).ok() == Some(m.id)
+}
+
+/// The number of a short match ID such as `M-07`.
+pub(crate) fn short_id_number(m: &Match) -> Option<u32> {
+    m.short_id.strip_prefix("M-").and_then(|n| n.parse::<u32>().ok())
+}
+
+/// Issues the short ID after `last_id`. IDs are never reused, even after a match is deleted.
+pub(crate) fn next_short_id(last_id: &mut u32) -> String {
+    *last_id += 1;
+    format!("M-{:02}", last_id)
+}
+
+/// Stores the number of the last short match ID issued in a guild.
+pub(crate) async fn save_match_counter(data: &mut TypeMap, guild_id: GuildId, last_id: u32) -> Result<(), CommandError> {
+    *data.guild_mut::<MatchCounter>(guild_id) = last_id;
+    write_to_file(&guild_file(guild_id, "match_counter.json"), serde_json::to_string(&last_id)?).await
+}
+
+pub(crate) fn current_season(seasons: &[Season]) -> Option<&Season> {
+    seasons.iter().find(|s| s.date_archived.is_none())
+}
+
+pub(crate) fn find_division<'a>(season: &'a Season, name: &str) -> Option<&'a Division> {
+    season.divisions.iter().find(|d| d.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Teams in seed order. Manual seeds set with `/admin seed` come first, followed by every other
+/// team in standings order.
+pub(crate) fn team_seeds(season: Option<&Season>, matches: &[Match]) -> Vec<RolePartial> {
+    let mut seeds: Vec<RolePartial> = season.map(|s| s.seeds.clone()).unwrap_or_default();
+    seeds.retain(|t| matches.iter().any(|m| m.team_one.id == t.id || m.team_two.id == t.id));
+    for standing in compute_standings(matches) {
+        if !seeds.iter().any(|t| t.id == standing.team.id) {
+            seeds.push(standing.team);
+        }
+    }
+    seeds
+}
 
-pub(crate) async fn write_to_file(path: &str, content: String) {
-    let mut error_string = String::from("Error writing to ");
-    error_string.push_str(path);
-    std::fs::write(path, content)
-        .expect(&error_string);
+/// The higher seeded of two teams, or `None` if neither team is seeded.
+pub(crate) fn higher_seed<'a>(seeds: &[RolePartial], team_one: &'a RolePartial, team_two: &'a RolePartial) -> Option<&'a RolePartial> {
+    let seed = |team: &RolePartial| seeds.iter().position(|t| t.id == team.id).unwrap_or(usize::MAX);
+    match seed(team_one).cmp(&seed(team_two)) {
+        std::cmp::Ordering::Less => Some(team_one),
+        std::cmp::Ordering::Greater => Some(team_two),
+        std::cmp::Ordering::Equal => None,
+    }
 }
 
-pub(crate) async fn find_user_team_role(all_guild_roles: Vec<Role>, user: &User, context: &&Context) -> Result<Role, String> {
+pub(crate) async fn find_user_team_role(all_guild_roles: Vec<Role>, user: &User, context: &&Context) -> Result<Role, CommandError> {
     let team_roles: Vec<Role> = all_guild_roles.into_iter().filter(|r| r.name.starts_with("Team")).collect();
     for team_role in team_roles {
         if let Ok(has_role) = user.has_role(&context.http, team_role.guild_id, team_role.id).await {
@@ -21,19 +254,28 @@ pub(crate) async fn find_user_team_role(all_guild_roles: Vec<Role>, user: &User,
             return Ok(team_role);
         }
     }
-    Err(String::from("User does not have a team role"))
+    Err(CommandError::User(String::from("You are not part of any team. Verify you have a role starting with `Team`")))
 }
 
-pub(crate) async fn is_phase_allowed(context: &Context, msg: &ApplicationCommandInteraction, state: State) -> Result<(), String> {
-    let mut data = context.data.write().await;
-    let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
+fn setup_teams(setup: &Setup) -> Result<(RolePartial, RolePartial), CommandError> {
+    match (&setup.team_one, &setup.team_two) {
+        (Some(team_one), Some(team_two)) => Ok((team_one.clone(), team_two.clone())),
+        _ => Err(CommandError::User(String::from("There is no `/setup` running"))),
+    }
+}
+
+pub(crate) async fn is_phase_allowed(context: &Context, msg: &ApplicationCommandInteraction, state: State) -> Result<(), CommandError> {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    let setup: &Setup = data.guild::<Setup>(guild_id);
     if setup.current_phase != state {
-        return Err(String::from("It is not the correct phase"));
+        return Err(CommandError::User(String::from("It is not the correct phase")));
     }
-    if let Ok(has_role_one) = msg.user.has_role(&context.http, msg.guild_id.unwrap(), setup.clone().team_one.unwrap().id).await {
-        if let Ok(has_role_two) = msg.user.has_role(&context.http, msg.guild_id.unwrap(), setup.clone().team_two.unwrap().id).await {
+    let (team_one, team_two) = setup_teams(setup)?;
+    if let Ok(has_role_one) = msg.user.has_role(&context.http, guild_id, team_one.id).await {
+        if let Ok(has_role_two) = msg.user.has_role(&context.http, guild_id, team_two.id).await {
             if !has_role_one && !has_role_two {
-                return Err(String::from("You are not part of either team currently running `/setup`"));
+                return Err(CommandError::User(String::from("You are not part of either team currently running `/setup`")));
             }
         }
     }
@@ -41,62 +283,307 @@ pub(crate) async fn is_phase_allowed(context: &Context, msg: &ApplicationCommand
 }
 
 
-pub(crate) async fn user_team(context: &Context, msg: &ApplicationCommandInteraction) -> Result<RolePartial, String> {
-    let mut data = context.data.write().await;
-    let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-    if let Ok(has_role_one) = msg.user.has_role(&context.http, msg.guild_id.unwrap(), setup.clone().team_one.unwrap().id).await {
-        if has_role_one { return Ok(setup.clone().team_one.unwrap()); }
-        if let Ok(has_role_two) = msg.user.has_role(&context.http, msg.guild_id.unwrap(), setup.clone().team_two.unwrap().id).await {
-            if has_role_two { return Ok(setup.clone().team_two.unwrap()); }
+pub(crate) async fn user_team(context: &Context, msg: &ApplicationCommandInteraction) -> Result<RolePartial, CommandError> {
+    let guild_id = guild_id(msg)?;
+    let data = context.data.read().await;
+    let (team_one, team_two) = setup_teams(data.guild::<Setup>(guild_id))?;
+    if let Ok(has_role_one) = msg.user.has_role(&context.http, guild_id, team_one.id).await {
+        if has_role_one { return Ok(team_one); }
+        if let Ok(has_role_two) = msg.user.has_role(&context.http, guild_id, team_two.id).await {
+            if has_role_two { return Ok(team_two); }
         }
     }
-    Err(String::from("You are not part of either team currently running `/setup`"))
+    Err(CommandError::User(String::from("You are not part of either team currently running `/setup`")))
 }
 
-pub(crate) async fn admin_check(context: &Context, inc_command: &ApplicationCommandInteraction) -> Result<String, String> {
-    let data = context.data.write().await;
+pub(crate) async fn admin_check(context: &Context, inc_command: &ApplicationCommandInteraction) -> Result<(), CommandError> {
+    let guild_id = guild_id(inc_command)?;
+    let data = context.data.read().await;
     let config: &Config = data.get::<Config>().unwrap();
-    if let Some(admin_role_id) = &config.discord.admin_role_id {
-        let role_name = context.cache.role(inc_command.guild_id.unwrap(), RoleId::from(*admin_role_id)).await.unwrap().name;
-        return if inc_command.user.has_role(&context.http, GuildContainer::from(inc_command.guild_id.unwrap()), RoleId::from(*admin_role_id)).await.unwrap_or(false) {
-            Ok(String::from("User has role"))
+    if let Some(admin_role_id) = &config.guild(guild_id).admin_role_id {
+        let role_name = context.cache.role(guild_id, RoleId::from(*admin_role_id)).await
+            .map(|role| role.name)
+            .unwrap_or_else(|| String::from("admin"));
+        return if inc_command.user.has_role(&context.http, GuildContainer::from(guild_id), RoleId::from(*admin_role_id)).await.unwrap_or(false) {
+            Ok(())
         } else {
-            Err(MessageBuilder::new()
+            Err(CommandError::User(MessageBuilder::new()
                 .mention(&inc_command.user)
                 .push(" this command requires the '")
                 .push(role_name)
                 .push("' role.")
-                .build())
+                .build()))
         };
     }
-    Ok(String::from("Admin Role not set, allowed"))
+    Ok(())
+}
+
+/// Checks the command was used in one of the guild's bot channels, or in a thread of one of them
+/// when threads are allowed.
+pub(crate) async fn channel_check(context: &Context, inc_command: &ApplicationCommandInteraction) -> Result<(), CommandError> {
+    let guild_id = guild_id(inc_command)?;
+    let config = context.data.read().await.get::<Config>().unwrap().guild(guild_id);
+    if config.channels.is_empty() || config.channels.contains(inc_command.channel_id.as_u64()) {
+        return Ok(());
+    }
+    if config.allow_threads {
+        if let Ok(Channel::Guild(channel)) = inc_command.channel_id.to_channel(context).await {
+            let parent = channel.category_id.map(|id| *id.as_u64());
+            if channel.thread_metadata.is_some() && parent.is_some_and(|id| config.channels.contains(&id)) {
+                return Ok(());
+            }
+        }
+    }
+    let channels: Vec<String> = config.channels.iter().map(|id| format!("<#{}>", id)).collect();
+    Err(CommandError::User(format!("Commands can only be used in {}", channels.join(", "))))
+}
+
+/// The channel new threads can be created in: the parent of a thread, or the channel itself.
+pub(crate) async fn thread_parent(context: &Context, channel_id: ChannelId) -> ChannelId {
+    match channel_id.to_channel(context).await {
+        Ok(Channel::Guild(channel)) if channel.thread_metadata.is_some() => channel.category_id.unwrap_or(channel_id),
+        _ => channel_id,
+    }
+}
+
+pub(crate) async fn update_brackets(data: &mut TypeMap, guild_id: GuildId, match_id: Uuid, winner: &RolePartial) -> Result<String, CommandError> {
+    let mut brackets = data.guild::<Brackets>(guild_id).clone();
+    let higher_seed_home = data.get::<Config>().unwrap().guild(guild_id).seeding.unwrap_or_default().home_slot;
+    let mut last_id = *data.guild::<MatchCounter>(guild_id);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let created = match advance_bracket(&mut brackets, matches, &mut last_id, match_id, winner, higher_seed_home) {
+        None => return Ok(String::new()),
+        Some(result) => result.map_err(CommandError::User)?,
+    };
+    write_to_file(&guild_file(guild_id, "brackets.json"), serde_json::to_string_pretty(&brackets)?).await?;
+    *data.guild_mut::<Brackets>(guild_id) = brackets;
+    if created.is_empty() {
+        return Ok(String::new());
+    }
+    save_match_counter(data, guild_id, last_id).await?;
+    let mut resp = String::from("\n\nNext bracket matches:\n");
+    for m in created {
+        resp.push_str(print_match_info(&m, false).as_str());
+    }
+    Ok(resp)
+}
+
+/// The map pool of a guild, either its own from the config or the shared pool from `maps.json`.
+pub(crate) fn guild_maps(data: &TypeMap, guild_id: GuildId) -> Vec<String> {
+    let maps = data.get::<Config>().unwrap().guild(guild_id).maps;
+    if maps.is_empty() { data.get::<Maps>().unwrap().clone() } else { maps }
+}
+
+pub(crate) async fn get_maps(context: &Context, guild_id: GuildId) -> Vec<String> {
+    let data = context.data.read().await;
+    guild_maps(&data, guild_id)
+}
+
+pub(crate) async fn finish_setup(context: &Context, inc_command: &ApplicationCommandInteraction) -> Result<(), CommandError> {
+    let guild_id = guild_id(inc_command)?;
+    let maps = get_maps(context, guild_id).await;
+    let mut data = context.data.write().await;
+    let before = audit_snapshot(&data, guild_id);
+    let setup_final: Setup = data.guild::<Setup>(guild_id).clone();
+    reset_setup(data.guild_mut::<Setup>(guild_id), maps);
+    let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+    let mut finished = None;
+    if let Some(m) = matches.iter_mut().find(|m| Some(m.id) == setup_final.match_id) {
+        m.setup_info = Some(SetupInfo { series_type: setup_final.series_type, maps: setup_final.maps, vetos: setup_final.veto_pick_order });
+        m.match_state = Completed;
+        finished = Some(m.clone());
+    }
+    write_to_file(&guild_file(guild_id, "matches.json"), serde_json::to_string_pretty(&matches)?).await?;
+    queue_audit(&mut data, guild_id, inc_command, before);
+    if let Some((thread_id, embed)) = finished.and_then(|m| Some((m.thread_id?, veto_embed(&m)?))) {
+        let posted = thread_id.send_message(&context.http, |message| message.set_embed(embed)).await;
+        if let Err(why) = posted {
+            eprintln!("Cannot post veto to match thread: {}", why);
+        }
+    }
+    Ok(())
+}
+
+/// Posts the result to a match's discussion thread and archives it.
+pub(crate) async fn close_match_thread(context: &Context, thread_id: Option<ChannelId>, content: &str) {
+    let thread_id = match thread_id {
+        Some(thread_id) => thread_id,
+        None => return,
+    };
+    if let Err(why) = thread_id.say(&context.http, content).await {
+        eprintln!("Cannot post result to match thread: {}", why);
+    }
+    if let Err(why) = thread_id.edit_thread(&context.http, |t| t.archived(true)).await {
+        eprintln!("Cannot archive match thread: {}", why);
+    }
+}
+
+
+fn lobby_overwrite(kind: PermissionOverwriteType, allow: Permissions) -> PermissionOverwrite {
+    PermissionOverwrite { allow, deny: Permissions::empty(), kind }
+}
+
+/// Creates a match's lobby category with a shared text channel and a voice channel per team.
+/// Only the two teams, the admin role, the match's casters and the bot can see the lobby.
+pub(crate) async fn create_match_lobby(context: &Context, guild_id: GuildId, m: &Match, admin_role_id: Option<u64>) -> serenity::Result<MatchLobby> {
+    let voice = Permissions::READ_MESSAGES | Permissions::CONNECT | Permissions::SPEAK;
+    let text = Permissions::READ_MESSAGES | Permissions::SEND_MESSAGES;
+    let mut staff = vec![
+        PermissionOverwrite { allow: Permissions::empty(), deny: Permissions::READ_MESSAGES | Permissions::CONNECT, kind: PermissionOverwriteType::Role(RoleId(*guild_id.as_u64())) },
+        lobby_overwrite(PermissionOverwriteType::Member(context.cache.current_user_id().await), voice | text | Permissions::MANAGE_CHANNELS),
+    ];
+    staff.extend(admin_role_id.map(|id| lobby_overwrite(PermissionOverwriteType::Role(RoleId(id)), voice | text)));
+    staff.extend(m.casters.iter().map(|c| lobby_overwrite(PermissionOverwriteType::Member(c.user_id), voice | text)));
+    let team_overwrite = |team: &RolePartial, allow: Permissions| lobby_overwrite(PermissionOverwriteType::Role(team.id), allow);
+
+    let category = guild_id.create_channel(&context.http, |c| {
+        c.name(match_title(m)).kind(ChannelType::Category).permissions(staff.clone())
+    }).await?;
+    let mut lobby = MatchLobby { category_id: category.id, channel_ids: Vec::new() };
+    let mut text_overwrites = staff.clone();
+    text_overwrites.push(team_overwrite(&m.team_one, text));
+    text_overwrites.push(team_overwrite(&m.team_two, text));
+    let channels = [
+        ("match-chat", ChannelType::Text, text_overwrites),
+        (m.team_one.name.as_str(), ChannelType::Voice, [staff.clone(), vec![team_overwrite(&m.team_one, voice)]].concat()),
+        (m.team_two.name.as_str(), ChannelType::Voice, [staff.clone(), vec![team_overwrite(&m.team_two, voice)]].concat()),
+    ];
+    for (name, kind, overwrites) in channels {
+        let created = guild_id.create_channel(&context.http, |c| {
+            c.name(name).kind(kind).category(category.id).permissions(overwrites)
+        }).await;
+        match created {
+            Ok(channel) => lobby.channel_ids.push(channel.id),
+            Err(why) => {
+                delete_lobby(context, &lobby).await;
+                return Err(why);
+            }
+        }
+    }
+    Ok(lobby)
 }
 
-pub(crate) async fn get_maps(context: &Context) -> Vec<String> {
-    let data = context.data.write().await;
-    let maps: &Vec<String> = data.get::<Maps>().unwrap();
-    maps.clone()
+/// Lets a caster into an existing lobby, or takes them out again when `allow` is false.
+pub(crate) async fn set_lobby_caster(context: &Context, lobby: &MatchLobby, user_id: UserId, allow: bool) {
+    let overwrite = lobby_overwrite(PermissionOverwriteType::Member(user_id), Permissions::READ_MESSAGES | Permissions::SEND_MESSAGES | Permissions::CONNECT | Permissions::SPEAK);
+    for channel_id in std::iter::once(&lobby.category_id).chain(lobby.channel_ids.iter()) {
+        let updated = if allow {
+            channel_id.create_permission(&context.http, &overwrite).await
+        } else {
+            channel_id.delete_permission(&context.http, overwrite.kind).await
+        };
+        if let Err(why) = updated {
+            eprintln!("Cannot update caster access to lobby channel {}: {}", channel_id, why);
+        }
+    }
 }
 
-pub(crate) async fn finish_setup(context: &Context) {
-    let maps = get_maps(context).await;
-    {
-        let mut data = context.data.write().await;
-        let setup_final: Setup = data.get::<Setup>().unwrap().clone();
-        if let Some(matches) = data.get_mut::<Matches>() {
-            let match_index = matches.iter().position(|m| m.id == setup_final.match_id.unwrap());
-            matches[match_index.unwrap()].setup_info = Some(SetupInfo { series_type: setup_final.series_type, maps: setup_final.maps, vetos: setup_final.veto_pick_order });
-            matches[match_index.unwrap()].match_state = Completed;
-            write_to_file("matches.json", serde_json::to_string_pretty(&matches).unwrap()).await;
+async fn delete_lobby(context: &Context, lobby: &MatchLobby) {
+    for channel_id in lobby.channel_ids.iter().chain(std::iter::once(&lobby.category_id)) {
+        if let Err(why) = channel_id.delete(&context.http).await {
+            eprintln!("Cannot delete lobby channel {}: {}", channel_id, why);
         }
     }
-    {
-        let mut data = context.data.write().await;
-        let setup: &mut Setup = data.get_mut::<Setup>().unwrap();
-        reset_setup(setup, maps);
+}
+
+/// Deletes a match's lobby once the guild's cleanup delay after `decided` has passed, also when
+/// the match has been deleted in the meantime. The data is only read by the spawned task, so this
+/// returns even while the caller holds the data lock.
+pub(crate) async fn schedule_lobby_cleanup(context: &Context, guild_id: GuildId, m: &Match, decided: DateTime<Utc>) {
+    if m.lobby.is_none() {
+        return;
     }
+    let (context, match_id) = (context.clone(), m.id);
+    tokio::spawn(async move {
+        let minutes = context.data.read().await.get::<Config>().unwrap().guild(guild_id).lobby.unwrap_or_default().cleanup_minutes;
+        let wait = (decided + chrono::Duration::minutes(minutes as i64) - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        let lobby = {
+            let mut data = context.data.write().await;
+            let matches: &mut Vec<Match> = data.guild_mut::<Matches>(guild_id);
+            let mut lobby = matches.iter_mut().find(|m| m.id == match_id).and_then(|m| m.lobby.take());
+            let mut saved = if lobby.is_some() { Some(("matches.json", serde_json::to_string_pretty(matches))) } else { None };
+            if lobby.is_none() {
+                let deleted: &mut Vec<DeletedMatch> = data.guild_mut::<DeletedMatches>(guild_id);
+                lobby = deleted.iter_mut().find(|d| d.deleted_match.id == match_id).and_then(|d| d.deleted_match.lobby.take());
+                if lobby.is_some() {
+                    saved = Some(("deleted_matches.json", serde_json::to_string_pretty(deleted)));
+                }
+            }
+            if let Some((file, Ok(json))) = saved {
+                if let Err(why) = write_to_file(&guild_file(guild_id, file), json).await {
+                    eprintln!("Cannot save lobby cleanup: {}", why);
+                }
+            }
+            lobby
+        };
+        if let Some(lobby) = lobby {
+            delete_lobby(&context, &lobby).await;
+        }
+    });
 }
 
+/// The data of a guild commands can change, compared before and after a command changes it.
+pub(crate) fn audit_snapshot(data: &TypeMap, guild_id: GuildId) -> AuditSnapshot {
+    AuditSnapshot {
+        matches: data.guild::<Matches>(guild_id).clone(),
+        deleted: data.guild::<DeletedMatches>(guild_id).clone(),
+        setup: data.guild::<Setup>(guild_id).clone(),
+        seasons: data.guild::<Seasons>(guild_id).clone(),
+        brackets: data.guild::<Brackets>(guild_id).clone(),
+        swiss: data.guild::<SwissTournaments>(guild_id).clone(),
+    }
+}
+
+/// Queues the changes a command made since `before`. Called under the same lock as the change, so
+/// changes made by other commands in the meantime are never attributed to it.
+pub(crate) fn queue_audit(data: &mut TypeMap, guild_id: GuildId, inc_command: &ApplicationCommandInteraction, before: AuditSnapshot) {
+    let after = audit_snapshot(data, guild_id);
+    let command = command_line(&inc_command.data.name, &inc_command.data.options);
+    let entries = audit_entries(&before, &after, inc_command.user.id, &command, Utc::now());
+    if !entries.is_empty() {
+        data.get_mut::<PendingAudit>().unwrap().entry(*inc_command.id.as_u64()).or_default().extend(entries);
+    }
+}
+
+/// Appends the changes queued by a command to the guild's audit file and posts them to the audit
+/// channel when one is configured.
+pub(crate) async fn record_audit(context: &Context, guild_id: GuildId, inc_command: &ApplicationCommandInteraction) {
+    let entries = context.data.write().await.get_mut::<PendingAudit>().unwrap().remove(inc_command.id.as_u64()).unwrap_or_default();
+    if entries.is_empty() {
+        return;
+    }
+    let lines: Result<Vec<String>, serde_json::Error> = entries.iter().map(serde_json::to_string).collect();
+    let appended = lines.map_err(CommandError::from).and_then(|lines| {
+        let path = guild_file(guild_id, "audit.jsonl");
+        if let Some(dir) = Path::new(&path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(format!("{}\n", lines.join("\n")).as_bytes())?;
+        Ok(())
+    });
+    if let Err(why) = appended {
+        eprintln!("Cannot write audit log: {}", why.internal_cause().unwrap_or_default());
+    }
+    let channel_id = context.data.read().await.get::<Config>().unwrap().guild(guild_id).audit_channel_id;
+    if let Some(channel_id) = channel_id {
+        let content: String = entries.iter().map(|e| format!("{}\n", print_audit_entry(e))).collect();
+        let posted = ChannelId(channel_id).send_message(&context.http, |message| {
+            message.content(content).allowed_mentions(|mentions| mentions.empty_parse())
+        }).await;
+        if let Err(why) = posted {
+            eprintln!("Cannot post to audit channel: {}", why);
+        }
+    }
+}
+
+/// Every audit entry of a guild, oldest first. Lines that cannot be parsed are skipped.
+pub(crate) fn read_audit(guild_id: GuildId) -> Vec<AuditEntry> {
+    std::fs::read_to_string(guild_file(guild_id, "audit.jsonl"))
+        .map(|content| content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
 
 pub(crate) fn print_veto_info(m: &Match) -> String {
     if m.setup_info.is_none() || m.setup_info.clone().unwrap().vetos.is_empty() {
@@ -118,29 +605,330 @@ pub(crate) fn print_veto_info(m: &Match) -> String {
     resp
 }
 
+pub(crate) async fn team_colour(context: &Context, team: &RolePartial) -> Option<Colour> {
+    context.cache.role(team.guild_id, team.id).await
+        .map(|role| role.colour)
+        .filter(|colour| colour.0 != 0)
+}
+
+fn map_score_str(m: &Match, map: &str) -> Option<String> {
+    m.result.as_ref()
+        .and_then(|r| r.map_scores.iter().find(|s| s.map.eq_ignore_ascii_case(map)))
+        .map(|s| format!("{}-{}", s.team_one_score, s.team_two_score))
+}
+
+fn match_summary(m: &Match) -> String {
+    let mut summary = String::new();
+    if let Some(schedule) = &m.schedule_info {
+        summary.push_str(format!("Scheduled: `{} @ {}`\n", schedule.date.format("%m/%d/%Y"), schedule.time_str).as_str());
+    }
+    if let Some(result) = &m.result {
+        if result.result_type == ResultType::Played {
+            let scores: Vec<String> = result.map_scores.iter().map(|s| format!("{} {}-{}", s.map.to_uppercase(), s.team_one_score, s.team_two_score)).collect();
+            summary.push_str(format!("Winner: **{}** `{}`", result.winner.name, scores.join(", ")).as_str());
+        } else {
+            summary.push_str(format!("Winner: **{}** `[{}]`", result.winner.name, result.result_type).as_str());
+        }
+        if let Some(reason) = &result.reason {
+            summary.push_str(format!(" - _{}_", reason).as_str());
+        }
+        summary.push('\n');
+    }
+    if let Some(note) = &m.note {
+        summary.push_str(format!("_{}_\n", note).as_str());
+    }
+    if summary.is_empty() {
+        summary.push_str("Not scheduled");
+    }
+    summary
+}
+
+fn match_title(m: &Match) -> String {
+    let mut week_str = String::new();
+    if let Some(week) = m.week {
+        week_str = format!(" Week {} -", week);
+    }
+    format!("{}{} {} vs {}", m.short_id, week_str, m.team_one.name, m.team_two.name)
+}
+
+/// An embed with a match's schedule, result and one field per map of its setup.
+pub(crate) fn match_embed(m: &Match, colour: Option<Colour>) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(match_title(m));
+    embed.description(match_summary(m));
+    if let Some(setup_info) = &m.setup_info {
+        for (i, series_map) in setup_info.maps.iter().enumerate() {
+            let mut value = format!("Picked by: <@&{}>", series_map.picked_by.id);
+            if let Some(team) = &series_map.start_attack {
+                value.push_str(format!("\nAttack start: <@&{}>", team.id).as_str());
+            }
+            if let Some(team) = &series_map.start_defense {
+                value.push_str(format!("\nDefense start: <@&{}>", team.id).as_str());
+            }
+            if let Some(score) = map_score_str(m, &series_map.map) {
+                value.push_str(format!("\nScore: `{}`", score).as_str());
+            }
+            embed.field(format!("Map {}: {}", i + 1, series_map.map.to_uppercase()), value, true);
+        }
+    }
+    if let Some(season) = &m.season {
+        embed.footer(|f| f.text(format!("{} | Match ID: {}", season, m.id)));
+    } else {
+        embed.footer(|f| f.text(format!("Match ID: {}", m.id)));
+    }
+    if !m.casters.is_empty() {
+        embed.field("Casters", casters_str(m), false);
+    }
+    for dispute in &m.disputes {
+        let status = match &dispute.resolution {
+            Some(resolution) => format!("`{}` {}", resolution.outcome, resolution.decision),
+            None => String::from("`OPEN`"),
+        };
+        embed.field(format!("Dispute by {}", dispute.team.name), format!("_{}_\n{}", dispute.reason, status), false);
+    }
+    if let Some(thread_id) = m.thread_id {
+        embed.field("Thread", format!("<#{}>", thread_id), false);
+    }
+    if let Some(colour) = colour {
+        embed.colour(colour);
+    }
+    embed
+}
+
+pub(crate) fn veto_embed(m: &Match) -> Option<CreateEmbed> {
+    let setup_info = m.setup_info.as_ref().filter(|s| !s.vetos.is_empty())?;
+    let mut embed = CreateEmbed::default();
+    embed.title("Map veto");
+    embed.description(setup_info.vetos.iter()
+        .map(|v| {
+            let action = if v.step_type == Veto { "banned" } else { "picked" };
+            format!("<@&{}> {} `{}`\n", v.team.id, action, v.map.clone().unwrap_or_default().to_uppercase())
+        })
+        .collect::<String>());
+    Some(embed)
+}
+
+/// One page of a match listing with a field per match.
+pub(crate) fn matches_page_embed(matches: &[&Match], page: usize, pages: usize, show_id: bool) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title("Matches");
+    for m in matches {
+        let mut value = match_summary(m);
+        if show_id { value.push_str(format!("\nMatch ID: `{}`", m.id).as_str()); }
+        embed.field(match_title(m), value, false);
+    }
+    embed.footer(|f| f.text(format!("Page {} of {}", page + 1, pages)));
+    embed
+}
+
+pub(crate) fn page_buttons(key: u64, page: usize, pages: usize) -> CreateComponents {
+    let mut components = CreateComponents::default();
+    components.create_action_row(|row| {
+        row.create_button(|button| button
+            .custom_id(format!("matches:{}:{}", key, page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0))
+            .create_button(|button| button
+                .custom_id(format!("matches:{}:{}", key, page + 1))
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= pages))
+    });
+    components
+}
+
 pub(crate) fn print_match_info(m: &Match, show_id: bool) -> String {
     let mut schedule_str = String::new();
     if let Some(schedule) = &m.schedule_info {
         schedule_str = format!(" > Scheduled: `{} @ {}`", schedule.date.format("%m/%d/%Y").to_string().as_str(), schedule.time_str.as_str());
     }
     let mut row = String::new();
-    row.push_str(format!("- {} vs {}{}", m.team_one.name, m.team_two.name, schedule_str).as_str());
+    let mut week_str = String::new();
+    if let Some(week) = m.week {
+        week_str = format!(" Week {} -", week);
+    }
+    row.push_str(format!("- `{}`{} {} vs {}{}", m.short_id, week_str, m.team_one.name, m.team_two.name, schedule_str).as_str());
     if m.note.is_some() {
         row.push_str(format!(" `{}`", m.note.clone().unwrap()).as_str());
     }
     row.push('\n');
+    if let Some(result) = &m.result {
+        if result.result_type == ResultType::Played {
+            let scores: Vec<String> = result.map_scores.iter().map(|s| format!("{} {}-{}", s.map.to_uppercase(), s.team_one_score, s.team_two_score)).collect();
+            row.push_str(format!("    Winner: **{}** `{}`", result.winner.name, scores.join(", ")).as_str());
+        } else {
+            row.push_str(format!("    Winner: **{}** `[{}]`", result.winner.name, result.result_type).as_str());
+        }
+        if let Some(reason) = &result.reason {
+            row.push_str(format!(" - _{}_", reason).as_str());
+        }
+        row.push('\n');
+    }
+    if !m.casters.is_empty() {
+        row.push_str(format!("    Casters: {}\n", casters_str(m)).as_str());
+    }
     if show_id { row.push_str(format!("    Match ID: `{}\n`", m.id).as_str()) }
     row
 }
 
-pub(crate) fn eos_printout(setup: Setup) -> String {
-    let mut resp = String::from("\n\nSetup is completed. GLHF!\n\n");
-    for (i, el) in setup.maps.iter().enumerate() {
-        resp.push_str(format!("**{}. {}** - picked by: <@&{}>\n    _Defense start:_ <@&{}>\n    _Attack start:_ <@&{}>\n\n", i + 1, el.map.to_uppercase(), &el.picked_by.id, el.start_defense.clone().unwrap().id, el.start_attack.clone().unwrap().id).as_str())
+fn casters_str(m: &Match) -> String {
+    m.casters.iter()
+        .map(|c| match &c.stream {
+            Some(stream) => format!("<@{}> ({})", c.user_id, stream),
+            None => format!("<@{}>", c.user_id),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Cast matches scheduled within the 7 days starting at `from`, ordered by date.
+pub(crate) fn broadcast_schedule(matches: &[Match], from: NaiveDate) -> String {
+    let until = from + chrono::Duration::days(7);
+    let mut cast: Vec<&Match> = matches.iter()
+        .filter(|m| !m.casters.is_empty() && m.result.is_none())
+        .filter(|m| m.schedule_info.as_ref().is_some_and(|s| s.date >= from && s.date < until))
+        .collect();
+    if cast.is_empty() {
+        return format!("No matches are being cast between {} and {}", from.format("%m/%d/%Y"), (until - chrono::Duration::days(1)).format("%m/%d/%Y"));
+    }
+    cast.sort_by_key(|m| m.schedule_info.as_ref().map(|s| s.date));
+    let mut resp = format!("**Broadcast schedule from {}**\n", from.format("%m/%d/%Y"));
+    for m in cast {
+        let schedule = m.schedule_info.as_ref().unwrap();
+        resp.push_str(format!("`{} @ {}` {} vs {} - {}\n", schedule.date.format("%a %m/%d"), schedule.time_str, m.team_one.name, m.team_two.name, casters_str(m)).as_str());
     }
     resp
 }
 
+/// Posts the coming week's broadcast schedule to the guild's schedule channel every Monday (UTC).
+pub(crate) fn schedule_weekly_broadcasts(context: &Context, guild_id: GuildId) {
+    let context = context.clone();
+    tokio::spawn(async move {
+        loop {
+            let today = Utc::now().date_naive();
+            let monday = today + chrono::Duration::days(7 - today.weekday().num_days_from_monday() as i64);
+            let wait = (monday.and_hms_opt(0, 0, 0).unwrap().and_utc() - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            let (channel_id, schedule) = {
+                let data = context.data.read().await;
+                let channel_id = data.get::<Config>().unwrap().guild(guild_id).cast.schedule_channel_id;
+                (channel_id, broadcast_schedule(data.guild::<Matches>(guild_id), monday))
+            };
+            if let Some(channel_id) = channel_id {
+                if let Err(why) = ChannelId(channel_id).say(&context.http, schedule).await {
+                    eprintln!("Cannot post broadcast schedule: {}", why);
+                }
+            }
+        }
+    });
+}
+
+/// Matches passing every filter. Unless sorted by date added, scheduled matches come first
+/// ordered by date, followed by unscheduled matches in the order they were added.
+pub(crate) fn filter_matches<'a>(matches: &'a [Match], filter: &MatchFilter) -> Vec<&'a Match> {
+    let scheduled_date = |m: &Match| m.schedule_info.as_ref().map(|s| s.date);
+    let mut filtered: Vec<&Match> = matches.iter()
+        .filter(|m| filter.states.contains(&m.match_state))
+        .filter(|m| filter.season.as_ref().is_none_or(|season| m.season.as_ref().map(|s| s.to_lowercase()) == *season))
+        .filter(|m| filter.division.is_none() || m.division.as_ref().map(|d| d.to_lowercase()) == filter.division)
+        .filter(|m| filter.week.is_none() || m.week == filter.week)
+        .filter(|m| filter.team.is_none() || Some(m.team_one.id) == filter.team || Some(m.team_two.id) == filter.team)
+        .filter(|m| filter.from.is_none() || scheduled_date(m).is_some_and(|date| Some(date) >= filter.from))
+        .filter(|m| filter.to.is_none() || scheduled_date(m).is_some_and(|date| Some(date) <= filter.to))
+        .collect();
+    if !filter.sort_by_added {
+        filtered.sort_by_key(|m| (scheduled_date(m).is_none(), scheduled_date(m)));
+    }
+    filtered
+}
+
+pub(crate) fn compute_standings(matches: &[Match]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = Vec::new();
+    for m in matches {
+        if let Some(result) = &m.result {
+            let loser = if result.winner.id == m.team_one.id { &m.team_two } else { &m.team_one };
+            let forfeited = result.result_type == ResultType::Forfeit || result.result_type == ResultType::NoShow;
+            if !standings.iter().any(|s| s.team.id == result.winner.id) {
+                standings.push(Standing { team: result.winner.clone(), wins: 0, losses: 0, forfeit_losses: 0 });
+            }
+            if !standings.iter().any(|s| s.team.id == loser.id) {
+                standings.push(Standing { team: loser.clone(), wins: 0, losses: 0, forfeit_losses: 0 });
+            }
+            let winner_entry = standings.iter_mut().find(|s| s.team.id == result.winner.id).unwrap();
+            winner_entry.wins += 1;
+            let loser_entry = standings.iter_mut().find(|s| s.team.id == loser.id).unwrap();
+            loser_entry.losses += 1;
+            if forfeited { loser_entry.forfeit_losses += 1; }
+        }
+    }
+    standings.sort_by(|a, b| b.wins.cmp(&a.wins).then(a.losses.cmp(&b.losses)));
+    standings
+}
+
+pub(crate) fn print_standings(matches: &[Match]) -> String {
+    let mut resp = String::from("Standings:\n");
+    for (i, standing) in compute_standings(matches).iter().enumerate() {
+        resp.push_str(format!("{}. {} - `{}W {}L`", i + 1, standing.team.name, standing.wins, standing.losses).as_str());
+        if standing.forfeit_losses > 0 {
+            resp.push_str(format!(" _({} by forfeit)_", standing.forfeit_losses).as_str());
+        }
+        resp.push('\n');
+    }
+    resp
+}
+
+fn percent(count: u32, total: u32) -> String {
+    if total == 0 { return String::from("-"); }
+    format!("{}%", count * 100 / total)
+}
+
+pub(crate) fn print_map_stats(setups: u32, stats: &[MapStat]) -> String {
+    let mut resp = format!("Map stats from {} setups:\n```\n{:<10} {:>5} {:>5} {:>7}\n", setups, "MAP", "PICK", "BAN", "PLAYED");
+    for stat in stats {
+        resp.push_str(format!("{:<10} {:>5} {:>5} {:>7}\n", stat.map.to_uppercase(), percent(stat.picks, setups), percent(stat.bans, setups), stat.played).as_str());
+    }
+    resp.push_str("```");
+    resp
+}
+
+pub(crate) fn print_team_stats(team: &RolePartial, stats: &TeamStats) -> String {
+    let top = |counts: Vec<(String, u32)>| -> String {
+        let mut counts: Vec<(String, u32)> = counts.into_iter().filter(|c| c.1 > 0).collect();
+        counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+        if counts.is_empty() { return String::from("-"); }
+        counts.iter().take(3).map(|c| format!("`{}` ({})", c.0.to_uppercase(), c.1)).collect::<Vec<String>>().join(", ")
+    };
+    let mut resp = format!("**{}** - {} completed setups\n", team.name, stats.setups);
+    resp.push_str(format!("Most banned: {}\n", top(stats.maps.iter().map(|s| (s.map.clone(), s.bans)).collect())).as_str());
+    resp.push_str(format!("Most picked: {}\n", top(stats.maps.iter().map(|s| (s.map.clone(), s.picks)).collect())).as_str());
+    resp.push_str(format!("First ban: {}\n", top(stats.first_bans.clone())).as_str());
+    resp.push_str(format!("```\n{:<10} {:>4} {:>4} {:>9} {:>9} {:>9}\n", "MAP", "PICK", "BAN", "WON", "ATK START", "DEF START").as_str());
+    for stat in &stats.maps {
+        let won = if stat.played == 0 { String::from("-") } else { format!("{}/{} {}", stat.wins, stat.played, percent(stat.wins, stat.played)) };
+        let attack = format!("{} ({}W)", stat.attack_starts, stat.attack_wins);
+        let defense = format!("{} ({}W)", stat.defense_starts, stat.defense_wins);
+        resp.push_str(format!("{:<10} {:>4} {:>4} {:>9} {:>9} {:>9}\n", stat.map.to_uppercase(), stat.picks, stat.bans, won, attack, defense).as_str());
+    }
+    resp.push_str("```");
+    resp
+}
+
+pub(crate) fn eos_embed(setup: Setup, colour: Option<Colour>) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("{} vs {}", setup.team_one.clone().unwrap().name, setup.team_two.clone().unwrap().name));
+    embed.description("Setup is completed. GLHF!");
+    for (i, el) in setup_summary(&setup).unwrap_or_default().iter().enumerate() {
+        embed.field(format!("{}. {}", i + 1, el.map.to_uppercase()),
+                    format!("Picked by: <@&{}>\nDefense start: <@&{}>\nAttack start: <@&{}>", &el.picked_by.id, el.start_defense.clone().unwrap().id, el.start_attack.clone().unwrap().id),
+                    true);
+    }
+    if let Some(colour) = colour {
+        embed.colour(colour);
+    }
+    embed
+}
+
 pub(crate) fn reset_setup(setup: &mut Setup, maps: Vec<String>) {
     setup.team_one = None;
     setup.team_two = None;
@@ -152,4 +940,185 @@ pub(crate) fn reset_setup(setup: &mut Setup, maps: Vec<String>) {
     setup.veto_pick_order = Vec::new();
     setup.current_step = 0;
     setup.current_phase = State::Idle;
+    setup.decider_side_pick = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use serenity::cache::Cache;
+    use serenity::client::bridge::gateway::ShardMessenger;
+    use serenity::http::Http;
+    use serenity::model::prelude::RoleId;
+    use serenity::prelude::RwLock;
+
+    use super::*;
+    use crate::{MatchResult, MatchState, ScheduleInfo};
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn new_match(team_one: RolePartial, team_two: RolePartial) -> Match {
+        Match {
+            id: Uuid::new_v4(),
+            short_id: String::new(),
+            team_one,
+            team_two,
+            note: None,
+            date_added: Utc::now(),
+            match_state: MatchState::Entered,
+            schedule_info: None,
+            setup_info: None,
+            result: None,
+            series_type: None,
+            season: None,
+            division: None,
+            week: None,
+            thread_id: None,
+            lobby: None,
+            casters: Vec::new(),
+            disputes: Vec::new(),
+        }
+    }
+
+    fn decided(team_one: RolePartial, team_two: RolePartial, winner: &RolePartial, result_type: ResultType) -> Match {
+        let mut m = new_match(team_one, team_two);
+        m.match_state = MatchState::Completed;
+        m.result = Some(MatchResult { winner: winner.clone(), result_type, map_scores: Vec::new(), reason: None, set_by: UserId(1), date_set: Utc::now() });
+        m
+    }
+
+    fn week(name: &str, value: i64) -> ApplicationCommandInteractionDataOption {
+        let mut option: ApplicationCommandInteractionDataOption = serde_json::from_value(serde_json::json!({"name": name, "type": 4, "value": value})).unwrap();
+        option.resolved = Some(ApplicationCommandInteractionDataOptionValue::Integer(value));
+        option
+    }
+
+    fn every_match() -> MatchFilter {
+        MatchFilter {
+            show_ids: false,
+            states: vec![MatchState::Entered, MatchState::Scheduled, MatchState::Completed],
+            season: None,
+            division: None,
+            week: None,
+            team: None,
+            from: None,
+            to: None,
+            sort_by_added: true,
+        }
+    }
+
+    fn test_context() -> Context {
+        let mut data = TypeMap::new();
+        data.insert::<Config>(serde_yaml::from_str("discord: {token: '', application_id: 1}").unwrap());
+        let (tx, _rx) = serenity::futures::channel::mpsc::unbounded();
+        Context {
+            data: Arc::new(RwLock::new(data)),
+            shard: ShardMessenger::new(tx),
+            shard_id: 0,
+            http: Arc::new(Http::new_with_token("")),
+            cache: Arc::new(Cache::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn lobby_cleanup_is_scheduled_while_the_data_lock_is_held() {
+        let context = test_context();
+        let mut m = new_match(team(1), team(2));
+        m.lobby = Some(MatchLobby { category_id: ChannelId(1), channel_ids: Vec::new() });
+        let _data = context.data.write().await;
+        let scheduled = tokio::time::timeout(Duration::from_secs(1), schedule_lobby_cleanup(&context, GuildId(1), &m, Utc::now())).await;
+        assert!(scheduled.is_ok(), "scheduling the lobby cleanup waited for the data lock");
+    }
+
+    #[test]
+    fn backups_rotate_by_guild_and_reason() {
+        assert_eq!(backup_reason("20240101-120000-scheduled"), "scheduled");
+        assert_eq!(backup_guild("20240101-120000-scheduled"), None);
+        assert_eq!(backup_reason("20240101-120000-42-admin-setresult"), "42-admin-setresult");
+        assert_eq!(backup_guild("20240101-120000-42-admin-setresult"), Some(GuildId(42)));
+        assert_ne!(backup_reason("20240101-120000-42-deletematch"), backup_reason("20240101-120000-43-deletematch"));
+    }
+
+    #[test]
+    fn standings_rank_by_wins_then_fewest_losses() {
+        let (t1, t2, t3, t4) = (team(1), team(2), team(3), team(4));
+        let matches = vec![
+            decided(t1.clone(), t2.clone(), &t1, ResultType::Played),
+            decided(t3.clone(), t1.clone(), &t1, ResultType::Played),
+            decided(t3.clone(), t2.clone(), &t3, ResultType::Forfeit),
+            decided(t4.clone(), t2.clone(), &t4, ResultType::NoShow),
+            new_match(t4.clone(), t3.clone()),
+        ];
+        let standings: Vec<(u64, u32, u32, u32)> = compute_standings(&matches).iter()
+            .map(|s| (s.team.id.0, s.wins, s.losses, s.forfeit_losses))
+            .collect();
+        assert_eq!(standings, vec![(1, 2, 0, 0), (4, 1, 0, 0), (3, 1, 1, 0), (2, 0, 3, 2)]);
+    }
+
+    #[test]
+    fn matches_are_found_by_short_id_or_uuid() {
+        let mut m = new_match(team(1), team(2));
+        let mut last_id = 6;
+        m.short_id = next_short_id(&mut last_id);
+        assert_eq!((m.short_id.as_str(), last_id), ("M-07", 7));
+        assert_eq!(short_id_number(&m), Some(7));
+        assert!(is_match_id(&m, " m-07 "));
+        assert!(is_match_id(&m, &m.id.to_string()));
+        assert!(!is_match_id(&m, "M-7"));
+        assert!(!is_match_id(&m, &Uuid::new_v4().to_string()));
+        last_id = 99;
+        assert_eq!(next_short_id(&mut last_id), "M-100");
+    }
+
+    #[test]
+    fn weeks_start_at_one() {
+        assert_eq!(week_option(&[week("week", 1)], "week").ok(), Some(Some(1)));
+        assert_eq!(week_option(&[week("week", u32::MAX as i64)], "week").ok(), Some(Some(u32::MAX)));
+        assert_eq!(week_option(&[week("startweek", 3)], "week").ok(), Some(None));
+        for value in [0, -2, u32::MAX as i64 + 1] {
+            assert!(matches!(week_option(&[week("startweek", value)], "startweek"), Err(CommandError::User(message)) if message == "`startweek` must be 1 or higher"));
+        }
+    }
+
+    #[test]
+    fn matches_without_a_season_are_listed_with_season_none() {
+        let mut spring = new_match(team(1), team(2));
+        spring.season = Some(String::from("Spring"));
+        let preseason = new_match(team(1), team(3));
+        let matches = vec![spring.clone(), preseason.clone()];
+        let ids = |filter: &MatchFilter| filter_matches(&matches, filter).iter().map(|m| m.id).collect::<Vec<Uuid>>();
+        assert_eq!(ids(&every_match()), vec![spring.id, preseason.id]);
+        assert_eq!(ids(&MatchFilter { season: Some(Some(String::from("spring"))), ..every_match() }), vec![spring.id]);
+        assert_eq!(ids(&MatchFilter { season: Some(None), ..every_match() }), vec![preseason.id]);
+    }
+
+    #[test]
+    fn filters_combine_and_scheduled_matches_come_first() {
+        let scheduled = |team_one: u64, team_two: u64, day: u32| {
+            let mut m = new_match(team(team_one), team(team_two));
+            m.match_state = MatchState::Scheduled;
+            m.division = Some(String::from("Gold"));
+            m.week = Some(2);
+            m.schedule_info = Some(ScheduleInfo { date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(), time_str: String::from("20:00") });
+            m
+        };
+        let late = scheduled(1, 2, 20);
+        let early = scheduled(3, 1, 5);
+        let unscheduled = Match { division: Some(String::from("Gold")), week: Some(2), ..new_match(team(4), team(1)) };
+        let other_week = Match { week: Some(3), ..scheduled(1, 3, 10) };
+        let other_team = scheduled(2, 3, 1);
+        let matches = vec![late.clone(), unscheduled.clone(), early.clone(), other_week, other_team];
+        let ids = |filter: &MatchFilter| filter_matches(&matches, filter).iter().map(|m| m.id).collect::<Vec<Uuid>>();
+        let filter = MatchFilter { division: Some(String::from("gold")), week: Some(2), team: Some(RoleId(1)), sort_by_added: false, ..every_match() };
+        assert_eq!(ids(&filter), vec![early.id, late.id, unscheduled.id]);
+        assert_eq!(ids(&MatchFilter { sort_by_added: true, ..filter.clone() }), vec![late.id, unscheduled.id, early.id]);
+        assert_eq!(ids(&MatchFilter { states: vec![MatchState::Scheduled], ..filter.clone() }), vec![early.id, late.id]);
+        let from = NaiveDate::from_ymd_opt(2024, 3, 10);
+        assert_eq!(ids(&MatchFilter { from, ..filter.clone() }), vec![late.id]);
+        assert_eq!(ids(&MatchFilter { to: from, ..filter }), vec![early.id]);
+    }
 }
@@ -0,0 +1,376 @@
+use std::fmt;
+
+use crate::{RolePartial, SeriesMap, SeriesType, Setup, SetupStep, State, TemplateStep, VetoTemplates};
+use crate::StepType::{Pick, Veto};
+
+/// Starting side picked for a map during the side pick phase.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Side {
+    Attack,
+    Defense,
+}
+
+/// What the acting team is expected to do next.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Turn {
+    Ban,
+    Pick,
+    Side,
+}
+
+/// How far the setup got after a step was applied.
+#[derive(PartialEq, Debug)]
+pub(crate) enum VetoProgress {
+    Next,
+    VetoFinished,
+    Completed,
+}
+
+#[derive(PartialEq, Debug)]
+pub(crate) enum VetoError {
+    WrongPhase,
+    NotYourTurn(Turn),
+    UnavailableMap,
+    InvalidTemplate,
+    VetoInProgress,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Side::Attack => "attack",
+            Side::Defense => "defense",
+        })
+    }
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Turn::Ban => "ban",
+            Turn::Pick => "pick",
+            Turn::Side => "pick sides",
+        })
+    }
+}
+
+impl fmt::Display for VetoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VetoError::WrongPhase => f.write_str("It is not the correct phase"),
+            VetoError::NotYourTurn(turn) => write!(f, "It is not your turn to {}", turn),
+            VetoError::UnavailableMap => f.write_str("Select a remaining map"),
+            VetoError::InvalidTemplate => f.write_str("The veto template for this series type does not fit the map pool, please contact an admin"),
+            VetoError::VetoInProgress => f.write_str("Another map veto is in progress. Wait for it to finish or ask an admin to `/cancel` it"),
+        }
+    }
+}
+
+/// Number of maps played in a series.
+pub(crate) fn series_length(series_type: &SeriesType) -> usize {
+    match series_type {
+        SeriesType::Bo1 => 1,
+        SeriesType::Bo3 => 3,
+        SeriesType::Bo5 => 5,
+    }
+}
+
+/// The ban and pick order of a series, the guild's template when it has one.
+fn step_order(series_type: &SeriesType, templates: &VetoTemplates) -> Vec<TemplateStep> {
+    use TemplateStep::{BanOne, BanTwo, PickOne, PickTwo};
+    let (template, default): (&Vec<TemplateStep>, &[TemplateStep]) = match series_type {
+        SeriesType::Bo1 => (&templates.bo1, &[BanTwo, BanOne, BanTwo, BanOne, BanTwo, PickOne]),
+        SeriesType::Bo3 => (&templates.bo3, &[BanOne, BanTwo, PickOne, PickTwo, BanTwo, PickOne]),
+        SeriesType::Bo5 => (&templates.bo5, &[BanOne, BanTwo, PickOne, PickTwo, PickOne, PickTwo, PickOne]),
+    };
+    if template.is_empty() { default.to_vec() } else { template.clone() }
+}
+
+fn veto_order(steps: &[TemplateStep], team_one: &RolePartial, team_two: &RolePartial) -> Vec<SetupStep> {
+    steps.iter()
+        .map(|step| {
+            let (step_type, team) = match step {
+                TemplateStep::BanOne => (Veto, team_one),
+                TemplateStep::BanTwo => (Veto, team_two),
+                TemplateStep::PickOne => (Pick, team_one),
+                TemplateStep::PickTwo => (Pick, team_two),
+            };
+            SetupStep { step_type, team: team.clone(), map: None }
+        })
+        .collect()
+}
+
+/// Starts the map veto between two teams. When `first` is given the teams are swapped if needed so
+/// that team takes the first step. The template has to pick every map of the series from the pool
+/// and only one veto can run at a time.
+pub(crate) fn start_veto(setup: &mut Setup, series_type: SeriesType, templates: &VetoTemplates, teams: (RolePartial, RolePartial), first: Option<&RolePartial>) -> Result<(), VetoError> {
+    if setup.current_phase != State::Idle {
+        return Err(VetoError::VetoInProgress);
+    }
+    let steps = step_order(&series_type, templates);
+    let picks = steps.iter().filter(|s| matches!(s, TemplateStep::PickOne | TemplateStep::PickTwo)).count();
+    if picks != series_length(&series_type) || steps.len() > setup.maps_remaining.len() {
+        return Err(VetoError::InvalidTemplate);
+    }
+    let (mut team_one, mut team_two) = teams;
+    let mut order = veto_order(&steps, &team_one, &team_two);
+    if first.is_some_and(|t| t.id != order[0].team.id) {
+        std::mem::swap(&mut team_one, &mut team_two);
+        order = veto_order(&steps, &team_one, &team_two);
+    }
+    setup.series_type = series_type;
+    setup.team_one = Some(team_one);
+    setup.team_two = Some(team_two);
+    setup.maps = Vec::new();
+    setup.veto_pick_order = order;
+    setup.current_step = 0;
+    setup.current_phase = State::MapVeto;
+    Ok(())
+}
+
+/// The team picking the starting side of the map at `index`: the decider side pick team on the last
+/// map if there is one, otherwise the team that did not pick the map.
+pub(crate) fn side_picker(setup: &Setup, index: usize) -> RolePartial {
+    if index == setup.maps.len() - 1 {
+        if let Some(team) = &setup.decider_side_pick {
+            return team.clone();
+        }
+    }
+    if setup.clone().team_two.unwrap().id == setup.maps[index].picked_by.id {
+        setup.clone().team_one.unwrap()
+    } else {
+        setup.clone().team_two.unwrap()
+    }
+}
+
+/// The next step of the setup and the team that has to take it, `None` once the setup is completed
+/// or when no setup is running.
+pub(crate) fn current_turn(setup: &Setup) -> Option<(Turn, RolePartial)> {
+    match setup.current_phase {
+        State::MapVeto => setup.veto_pick_order.get(setup.current_step).map(|step| {
+            let turn = if step.step_type == Veto { Turn::Ban } else { Turn::Pick };
+            (turn, step.team.clone())
+        }),
+        State::SidePick if setup.current_step < setup.maps.len() => Some((Turn::Side, side_picker(setup, setup.current_step))),
+        _ => None,
+    }
+}
+
+fn validate_turn(setup: &Setup, team: &RolePartial, turn: Turn) -> Result<(), VetoError> {
+    match current_turn(setup) {
+        None => Err(VetoError::WrongPhase),
+        Some((current, _)) if (current == Turn::Side) != (turn == Turn::Side) => Err(VetoError::WrongPhase),
+        Some((current, actor)) if current != turn || actor.id != team.id => Err(VetoError::NotYourTurn(turn)),
+        _ => Ok(()),
+    }
+}
+
+/// Bans or picks `map` for `team`. The setup moves on to the side pick phase after the last step.
+pub(crate) fn apply_map_step(setup: &mut Setup, team: &RolePartial, turn: Turn, map: &str) -> Result<VetoProgress, VetoError> {
+    validate_turn(setup, team, turn)?;
+    let map_index = setup.maps_remaining.iter().position(|m| m == map).ok_or(VetoError::UnavailableMap)?;
+    let map = setup.maps_remaining.remove(map_index);
+    let step = &mut setup.veto_pick_order[setup.current_step];
+    step.map = Some(map.clone());
+    if step.step_type == Pick {
+        let picked_by = step.team.clone();
+        setup.maps.push(SeriesMap { map, picked_by, start_attack: None, start_defense: None });
+    }
+    setup.current_step += 1;
+    if setup.current_step < setup.veto_pick_order.len() {
+        return Ok(VetoProgress::Next);
+    }
+    setup.current_phase = State::SidePick;
+    setup.current_step = 0;
+    Ok(VetoProgress::VetoFinished)
+}
+
+/// Sets the starting side `team` picked for the current map, the other team starts on the other side.
+pub(crate) fn apply_side_pick(setup: &mut Setup, team: &RolePartial, side: Side) -> Result<VetoProgress, VetoError> {
+    validate_turn(setup, team, Turn::Side)?;
+    let other = if setup.team_one.as_ref().is_some_and(|t| t.id == team.id) { setup.team_two.clone() } else { setup.team_one.clone() };
+    let map = &mut setup.maps[setup.current_step];
+    match side {
+        Side::Attack => {
+            map.start_attack = Some(team.clone());
+            map.start_defense = other;
+        }
+        Side::Defense => {
+            map.start_defense = Some(team.clone());
+            map.start_attack = other;
+        }
+    }
+    setup.current_step += 1;
+    if setup.current_step < setup.maps.len() {
+        return Ok(VetoProgress::Next);
+    }
+    Ok(VetoProgress::Completed)
+}
+
+/// The maps of the series with both starting sides set, once every step of the setup is done.
+pub(crate) fn setup_summary(setup: &Setup) -> Option<&[SeriesMap]> {
+    let completed = setup.current_phase == State::SidePick
+        && !setup.maps.is_empty()
+        && setup.current_step >= setup.maps.len()
+        && setup.maps.iter().all(|m| m.start_attack.is_some() && m.start_defense.is_some());
+    if completed { Some(&setup.maps) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serenity::model::prelude::{GuildId, RoleId};
+
+    use super::*;
+
+    fn team(id: u64) -> RolePartial {
+        RolePartial { id: RoleId(id), name: format!("Team {}", id), guild_id: GuildId(1) }
+    }
+
+    fn new_setup(map_count: usize) -> Setup {
+        Setup {
+            team_one: None,
+            team_two: None,
+            maps_remaining: (0..map_count).map(|i| format!("map{}", i)).collect(),
+            maps: Vec::new(),
+            vetos: Vec::new(),
+            series_type: SeriesType::Bo3,
+            match_id: None,
+            veto_pick_order: Vec::new(),
+            current_step: 0,
+            current_phase: State::Idle,
+            decider_side_pick: None,
+        }
+    }
+
+    fn series_type() -> impl Strategy<Value = SeriesType> {
+        prop_oneof![Just(SeriesType::Bo1), Just(SeriesType::Bo3), Just(SeriesType::Bo5)]
+    }
+
+    fn other_team(setup: &Setup, actor: &RolePartial) -> RolePartial {
+        if setup.team_one.as_ref().unwrap().id == actor.id { setup.team_two.clone().unwrap() } else { setup.team_one.clone().unwrap() }
+    }
+
+    #[test]
+    fn higher_seed_bans_first_in_bo1_and_the_other_team_picks_the_map() {
+        let mut setup = new_setup(7);
+        start_veto(&mut setup, SeriesType::Bo1, &VetoTemplates::default(), (team(3), team(2)), Some(&team(3))).unwrap();
+        let order: Vec<(bool, u64)> = setup.veto_pick_order.iter().map(|s| (s.step_type == Pick, s.team.id.0)).collect();
+        assert_eq!(order, vec![(false, 3), (false, 2), (false, 3), (false, 2), (false, 3), (true, 2)]);
+        assert_eq!(setup.team_two.as_ref().map(|t| t.id.0), Some(3));
+    }
+
+    proptest! {
+        #[test]
+        fn every_series_runs_to_completion(
+            series in series_type(),
+            extra_maps in 0usize..4,
+            choices in proptest::collection::vec(any::<prop::sample::Index>(), 7),
+            sides in proptest::collection::vec(any::<bool>(), 5),
+            higher_first in any::<bool>(),
+            decider in any::<bool>(),
+        ) {
+            let order_len = step_order(&series, &VetoTemplates::default()).len();
+            let mut setup = new_setup(order_len + extra_maps);
+            let first = if higher_first { Some(team(3)) } else { None };
+            start_veto(&mut setup, series.clone(), &VetoTemplates::default(), (team(2), team(3)), first.as_ref()).unwrap();
+            setup.decider_side_pick = if decider { Some(team(3)) } else { None };
+            if let Some(first) = &first {
+                prop_assert_eq!(setup.veto_pick_order[0].team.id, first.id);
+            }
+
+            for choice in choices.iter().take(order_len) {
+                let (turn, actor) = current_turn(&setup).unwrap();
+                prop_assert!(turn != Turn::Side);
+                let other = other_team(&setup, &actor);
+                let map = setup.maps_remaining[choice.index(setup.maps_remaining.len())].clone();
+                prop_assert_eq!(apply_map_step(&mut setup, &other, turn, &map), Err(VetoError::NotYourTurn(turn)));
+                prop_assert_eq!(apply_map_step(&mut setup, &actor, turn, "not a map"), Err(VetoError::UnavailableMap));
+                prop_assert_eq!(apply_side_pick(&mut setup, &actor, Side::Attack), Err(VetoError::WrongPhase));
+                let progress = apply_map_step(&mut setup, &actor, turn, &map).unwrap();
+                prop_assert!(!setup.maps_remaining.contains(&map));
+                prop_assert_eq!(progress == VetoProgress::VetoFinished, setup.current_phase == State::SidePick);
+            }
+            prop_assert!(setup.current_phase == State::SidePick);
+            prop_assert_eq!(setup.maps.len(), series_length(&series));
+            prop_assert_eq!(setup.maps_remaining.len(), extra_maps);
+            prop_assert!(setup.veto_pick_order.iter().all(|s| s.map.is_some()));
+
+            let map_count = setup.maps.len();
+            for (i, pick_attack) in sides.iter().take(map_count).enumerate() {
+                prop_assert!(setup_summary(&setup).is_none());
+                let (turn, actor) = current_turn(&setup).unwrap();
+                prop_assert_eq!(turn, Turn::Side);
+                if i == map_count - 1 && decider {
+                    prop_assert_eq!(actor.id, team(3).id);
+                } else {
+                    prop_assert!(actor.id != setup.maps[i].picked_by.id);
+                }
+                let other = other_team(&setup, &actor);
+                prop_assert_eq!(apply_side_pick(&mut setup, &other, Side::Defense), Err(VetoError::NotYourTurn(Turn::Side)));
+                prop_assert_eq!(apply_map_step(&mut setup, &actor, Turn::Pick, "map0"), Err(VetoError::WrongPhase));
+                let side = if *pick_attack { Side::Attack } else { Side::Defense };
+                let progress = apply_side_pick(&mut setup, &actor, side).unwrap();
+                prop_assert_eq!(progress == VetoProgress::Completed, i == map_count - 1);
+                let map = &setup.maps[i];
+                let (picked, unpicked) = if side == Side::Attack { (&map.start_attack, &map.start_defense) } else { (&map.start_defense, &map.start_attack) };
+                prop_assert_eq!(picked.as_ref().unwrap().id, actor.id);
+                prop_assert_eq!(unpicked.as_ref().unwrap().id, other.id);
+            }
+            prop_assert!(current_turn(&setup).is_none());
+            let summary = setup_summary(&setup).unwrap();
+            prop_assert_eq!(summary.len(), series_length(&series));
+            for map in summary {
+                prop_assert!(map.start_attack.as_ref().unwrap().id != map.start_defense.as_ref().unwrap().id);
+            }
+        }
+
+        #[test]
+        fn wrong_step_type_is_rejected(series in series_type()) {
+            let mut setup = new_setup(step_order(&series, &VetoTemplates::default()).len());
+            start_veto(&mut setup, series, &VetoTemplates::default(), (team(2), team(3)), None).unwrap();
+            let (turn, actor) = current_turn(&setup).unwrap();
+            let wrong = if turn == Turn::Ban { Turn::Pick } else { Turn::Ban };
+            prop_assert_eq!(apply_map_step(&mut setup, &actor, wrong, "map0"), Err(VetoError::NotYourTurn(wrong)));
+            prop_assert_eq!(setup.current_step, 0);
+            prop_assert!(setup.veto_pick_order.iter().all(|s| s.map.is_none()));
+        }
+
+        #[test]
+        fn running_veto_is_not_replaced(series in series_type(), steps_taken in 0usize..3) {
+            let mut setup = new_setup(9);
+            start_veto(&mut setup, series.clone(), &VetoTemplates::default(), (team(2), team(3)), None).unwrap();
+            for _ in 0..steps_taken {
+                let (turn, actor) = current_turn(&setup).unwrap();
+                let map = setup.maps_remaining[0].clone();
+                apply_map_step(&mut setup, &actor, turn, &map).unwrap();
+            }
+            let before = (setup.current_step, setup.maps_remaining.clone(), setup.team_one.clone().unwrap().id);
+            prop_assert_eq!(start_veto(&mut setup, series, &VetoTemplates::default(), (team(4), team(5)), None), Err(VetoError::VetoInProgress));
+            prop_assert_eq!((setup.current_step, setup.maps_remaining.clone(), setup.team_one.clone().unwrap().id), before);
+        }
+
+        #[test]
+        fn templates_set_the_order(steps in proptest::collection::vec(any::<prop::sample::Index>(), 1..9)) {
+            let options = [TemplateStep::BanOne, TemplateStep::BanTwo, TemplateStep::PickOne, TemplateStep::PickTwo];
+            let steps: Vec<TemplateStep> = steps.iter().map(|i| options[i.index(options.len())]).collect();
+            let templates = VetoTemplates { bo3: steps.clone(), ..VetoTemplates::default() };
+            let mut setup = new_setup(8);
+            let result = start_veto(&mut setup, SeriesType::Bo3, &templates, (team(2), team(3)), None);
+            let picks = steps.iter().filter(|s| matches!(s, TemplateStep::PickOne | TemplateStep::PickTwo)).count();
+            if picks != 3 {
+                prop_assert_eq!(result, Err(VetoError::InvalidTemplate));
+                prop_assert!(setup.current_phase == State::Idle);
+            } else {
+                prop_assert_eq!(result, Ok(()));
+                prop_assert_eq!(setup.veto_pick_order.len(), steps.len());
+                for (step, template) in setup.veto_pick_order.iter().zip(&steps) {
+                    let team_one = matches!(template, TemplateStep::BanOne | TemplateStep::PickOne);
+                    let ban = matches!(template, TemplateStep::BanOne | TemplateStep::BanTwo);
+                    prop_assert_eq!(step.team.id, if team_one { team(2).id } else { team(3).id });
+                    prop_assert_eq!(step.step_type == Veto, ban);
+                }
+            }
+        }
+    }
+}